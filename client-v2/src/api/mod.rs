@@ -1,10 +1,18 @@
-use data::{configdata::ConfigContest, ContestFile, RunTuple, TimerData};
-use futures::{channel::mpsc::UnboundedReceiver, StreamExt};
+use data::{
+    balloons::Balloon, configdata::ConfigContest, wire::WireFormat, ContestFile, RunTuple,
+    TimerData,
+};
+use futures::{channel::mpsc::UnboundedReceiver, Stream, StreamExt};
+use gloo_net::http::Request;
 
 use leptos::{logging::*, prelude::*, task::spawn_local};
 use leptos_router::params::Params;
 
-use crate::net::{request_signal::create_request, websocket_stream::create_websocket_stream};
+use crate::net::{
+    request_signal::create_request,
+    websocket_stream::{create_resumable_stream, create_websocket_stream},
+    wire_format,
+};
 
 const DEFAULT_URL: &str = "http://0.0.0.0";
 
@@ -110,17 +118,64 @@ pub async fn create_config(query: ContestQuery) -> ConfigContest {
     config_message
 }
 
-pub fn create_runs(query: ContestQuery) -> UnboundedReceiver<RunTuple> {
-    create_websocket_stream::<RunTuple>(&contest_query_ws("allruns_ws", query))
+pub fn create_runs(query: ContestQuery) -> impl Stream<Item = RunTuple> {
+    let mut ws_url = contest_query_ws("allruns_ws", query.clone());
+    let format = wire_format();
+    if format != WireFormat::Json {
+        let separator = if ws_url.contains('?') { '&' } else { '?' };
+        ws_url.push_str(&format!("{separator}format={}", format.as_str()));
+    }
+
+    create_resumable_stream::<RunTuple>(&ws_url, &url("allruns_poll", query))
+        .map(|message| message.value)
 }
 
-pub fn remote_control_url(key: &str) -> String {
+/// Versions of the server config, sent when it is reloaded.
+pub fn create_config_updates() -> UnboundedReceiver<u64> {
+    create_websocket_stream::<u64>(
+        &contest_query_ws("config_ws", ContestQuery { contest: None }),
+        None,
+    )
+}
+
+pub fn remote_control_url(key: &str, token: Option<&str>) -> String {
     let mut prefix = ws_url_prefix();
     prefix.push_str("/remote_control/");
     prefix.push_str(key);
+    if let Some(token) = token {
+        prefix.push_str("?token=");
+        prefix.push_str(token);
+    }
     prefix
 }
 
+/// Follows a remote control by polling, when its websocket is blocked,
+/// resuming after the last sequence received.
+pub fn remote_control_poll_url(key: &str, since: Option<u64>) -> String {
+    let mut prefix = url_prefix();
+    prefix.push_str("/remote_control_poll/");
+    prefix.push_str(key);
+    if let Some(since) = since {
+        prefix.push_str(&format!("?since={since}"));
+    }
+    prefix
+}
+
+pub fn revelation_session_url(name: &str, secret: &str, contest: Option<String>) -> String {
+    let mut url = ws_url_prefix();
+    url.push_str("/revelation/");
+    url.push_str(name);
+    url.push_str("?secret=");
+    url.push_str(secret);
+
+    if let Some(contest) = contest {
+        url.push_str("&contest=");
+        url.push_str(contest.as_str());
+    }
+
+    url
+}
+
 pub async fn create_secret_runs(secret: String, contest: Option<String>) -> data::RunsFile {
     let mut url = url_prefix();
     url.push_str("/allruns_secret?secret=");
@@ -134,11 +189,63 @@ pub async fn create_secret_runs(secret: String, contest: Option<String>) -> data
     create_request(&url).await
 }
 
-pub fn create_timer() -> ReadSignal<(TimerData, TimerData)> {
-    let mut timer_stream = create_websocket_stream::<TimerData>(&contest_query_ws(
-        "timer",
-        ContestQuery { contest: None },
-    ));
+fn balloons_url(path: &str, secret: &str, contest: Option<String>) -> String {
+    let mut url = url_prefix();
+    url.push('/');
+    url.push_str(path);
+    url.push_str("?secret=");
+    url.push_str(secret);
+
+    if let Some(contest) = contest {
+        url.push_str("&contest=");
+        url.push_str(contest.as_str());
+    }
+
+    url
+}
+
+pub async fn create_balloons(secret: String, contest: Option<String>) -> Vec<Balloon> {
+    create_request(&balloons_url("balloons", &secret, contest)).await
+}
+
+pub async fn set_balloon_delivered(
+    run_id: i64,
+    delivered: bool,
+    secret: String,
+    contest: Option<String>,
+) -> Result<(), gloo_net::Error> {
+    let url = balloons_url(&format!("balloons/{run_id}"), &secret, contest);
+    let resp = Request::put(&url)
+        .json(&serde_json::json!({ "delivered": delivered }))?
+        .send()
+        .await?;
+
+    if !resp.ok() {
+        warn!("failed delivering balloon {run_id}: {}", resp.status());
+    }
+    Ok(())
+}
+
+/// The contest of the page, for code running outside the router.
+pub fn location_contest_query() -> ContestQuery {
+    let search = web_sys::window()
+        .and_then(|w| w.location().search().ok())
+        .unwrap_or_default();
+
+    let contest = search
+        .trim_start_matches('?')
+        .split('&')
+        .find_map(|pair| pair.strip_prefix("contest="))
+        .map(|contest| contest.to_string());
+
+    ContestQuery { contest }
+}
+
+pub fn create_timer(query: ContestQuery) -> ReadSignal<(TimerData, TimerData)> {
+    let mut timer_stream = create_websocket_stream::<TimerData>(
+        &contest_query_ws("timer", query.clone()),
+        Some(&url("timer_poll", query)),
+    );
 
     let (timer, set_timer) = signal((TimerData::fake(), data::TimerData::new(0, 1)));
 
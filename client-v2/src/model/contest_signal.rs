@@ -22,7 +22,12 @@ impl ContestSignal {
             teams: contest_file
                 .teams
                 .iter()
-                .map(|(login, team)| (login.clone(), Arc::new(TeamSignal::new(team, &letters))))
+                .map(|(login, team)| {
+                    (
+                        login.clone(),
+                        Arc::new(TeamSignal::new(team, &letters, contest_file.scoring)),
+                    )
+                })
                 .collect(),
             team_global_placements: RwSignal::new(
                 contest_file
@@ -44,7 +49,7 @@ impl ContestSignal {
         for team in fresh_contest.teams.values() {
             if let Some(team_signal) = self.teams.get(&team.login) {
                 if update_set.contains(team.login.as_str()) {
-                    team_signal.update(team);
+                    team_signal.update(team, fresh_contest.scoring);
                 } else {
                     team_signal.placement_global.set(team.placement_global)
                 }
@@ -55,6 +55,7 @@ pub async fn provide_contest(query: ContestQuery) -> ContestProvider {
     let runs_panel_item_manager_ref = runs_panel_item_manager.clone();
 
     let mut running_contest = starting_contest.clone();
+    let rejudged_contest = starting_contest.clone();
 
     spawn_local(async move {
         let mut runs_file = RunsFile::empty();
@@ -71,12 +72,25 @@ pub async fn provide_contest(query: ContestQuery) -> ContestProvider {
             if let Some(mut next_batch) = next_batch {
                 annotate_first_solved(&mut solved, next_batch.iter_mut());
                 let mut fresh_runs = vec![];
+                let mut rejudged = false;
                 for run_tuple in next_batch {
+                    let known = runs_file.get(run_tuple.id).is_some();
                     if runs_file.refresh_1(&run_tuple) {
+                        rejudged |= known;
                         fresh_runs.push(run_tuple);
                     }
                 }
 
+                // A run judged again, or hidden, may undo what was applied, so the contest is scored again.
+                if rejudged {
+                    running_contest = rejudged_contest.clone();
+                    for r in runs_file.sorted() {
+                        if !fresh_runs.iter().any(|fresh| fresh.id == r.id) {
+                            running_contest.apply_run(&r);
+                        }
+                    }
+                }
+
                 if !fresh_runs.is_empty() {
                     let runs = runs_file.sorted();
 
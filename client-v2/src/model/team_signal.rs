@@ -1,6 +1,6 @@
 use std::collections::HashMap;
 
-use data::{Letter, ProblemView, Score, Team};
+use data::{scoring::ScoringPolicy, Letter, ProblemView, Score, Team};
 use itertools::Itertools;
 use leptos::prelude::*;
 
@@ -14,7 +14,7 @@ pub struct TeamSignal {
 }
 
 impl TeamSignal {
-    pub fn new(team: &Team, letters: &[Letter]) -> Self {
+    pub fn new(team: &Team, letters: &[Letter], policy: ScoringPolicy) -> Self {
         let Team {
             login,
             escola,
@@ -30,7 +30,7 @@ impl TeamSignal {
             name: name.clone(),
             escola: escola.clone(),
             placement_global: RwSignal::new(*placement_global),
-            score: RwSignal::new(team.score()),
+            score: RwSignal::new(team.score(policy)),
             problems: letters
                 .iter()
                 .map(|l| {
@@ -41,8 +41,8 @@ impl TeamSignal {
         }
     }
 
-    pub fn update(&self, team: &Team) {
-        let new_score = team.score();
+    pub fn update(&self, team: &Team, policy: ScoringPolicy) {
+        let new_score = team.score(policy);
         self.score.update(|x| *x = new_score);
         self.placement_global.update(|p| *p = team.placement_global);
 
@@ -1,2 +1,12 @@
+use data::wire::WireFormat;
+
 pub mod request_signal;
 pub mod websocket_stream;
+
+/// The format asked for to the server, JSON unless built with `WIRE_FORMAT=msgpack`.
+pub fn wire_format() -> WireFormat {
+    match option_env!("WIRE_FORMAT") {
+        Some("msgpack") => WireFormat::Msgpack,
+        _ => WireFormat::Json,
+    }
+}
@@ -1,18 +1,29 @@
+use data::wire::{WireError, WireFormat};
 use gloo_net::http::Request;
 use gloo_timers::future::TimeoutFuture;
 use leptos::logging::*;
 use serde::{Deserialize, Serialize};
 
 #[derive(Debug)]
-enum Error {
+pub(crate) enum Error {
     Gloo(gloo_net::Error),
-    Serde(serde_json::Error),
+    Decode(WireError),
 }
 
-async fn get_url<M: for<'a> Deserialize<'a>>(url: &str) -> Result<M, Error> {
-    let resp = Request::get(url).send().await.map_err(Error::Gloo)?;
-    let text = resp.text().await.map_err(Error::Gloo)?;
-    let message = serde_json::from_str(&text).map_err(Error::Serde)?;
+pub(crate) async fn get_url<M: for<'a> Deserialize<'a>>(url: &str) -> Result<M, Error> {
+    let resp = Request::get(url)
+        .header("Accept", super::wire_format().content_type())
+        .send()
+        .await
+        .map_err(Error::Gloo)?;
+    // Endpoints that do not support other formats still answer in JSON.
+    let format = resp
+        .headers()
+        .get("Content-Type")
+        .map(|content_type| WireFormat::from_content_type(&content_type))
+        .unwrap_or_default();
+    let bytes = resp.binary().await.map_err(Error::Gloo)?;
+    let message = format.decode(&bytes).map_err(Error::Decode)?;
 
     Ok(message)
 }
@@ -30,8 +41,8 @@ pub async fn create_request<M: for<'a> Deserialize<'a> + Serialize + Clone>(url:
             Err(error) => {
                 match error {
                     Error::Gloo(gloo) => error!("network error: {gloo:?}"),
-                    Error::Serde(serde) => {
-                        error!("failed to parse response: {serde:?}")
+                    Error::Decode(decode) => {
+                        error!("failed to parse response: {decode:?}")
                     }
                 }
                 log!("Wait 5 seconds to reconnect.");
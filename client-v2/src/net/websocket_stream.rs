@@ -1,5 +1,11 @@
+use std::{cell::Cell, rc::Rc};
+
+use data::{
+    sequenced::Sequenced,
+    wire::{WireError, WireFormat},
+};
 use futures::{
-    channel::mpsc::{self, UnboundedReceiver},
+    channel::mpsc::{self, UnboundedReceiver, UnboundedSender},
     SinkExt, StreamExt,
 };
 use gloo_net::websocket::{futures::WebSocket, Message, WebSocketError};
@@ -8,9 +14,11 @@ use leptos::leptos_dom::logging::{console_error, console_log, console_warn};
 use serde::Deserialize;
 use wasm_bindgen_futures::spawn_local;
 
+use super::request_signal::get_url;
+
 #[derive(Debug)]
 enum Error {
-    Serde(serde_json::Error),
+    Decode(WireError),
     WebSocket(WebSocketError),
     EmptyMessage,
 }
@@ -20,34 +28,88 @@ fn parse_message<M: for<'a> Deserialize<'a>>(
 ) -> Result<M, Error> {
     let message = ws_message.ok_or(Error::EmptyMessage)?;
     let msg = message.map_err(Error::WebSocket)?;
-    Ok(match &msg {
-        Message::Text(txt) => serde_json::from_str(txt).map_err(Error::Serde)?,
-        Message::Bytes(bytes) => serde_json::from_slice(bytes).map_err(Error::Serde)?,
-    })
+    match &msg {
+        Message::Text(txt) => WireFormat::Json.decode(txt.as_bytes()),
+        // The server only sends binary messages in MessagePack.
+        Message::Bytes(bytes) => WireFormat::Msgpack.decode(bytes),
+    }
+    .map_err(Error::Decode)
 }
 
+/// Websocket connections that fail, without receiving anything,
+/// before falling back to polling.
+const WEBSOCKET_ATTEMPTS: usize = 3;
+
+/// A websocket stream, that falls back to polling `poll_url`
+/// when websockets are blocked, e.g. by a proxy.
 pub fn create_websocket_stream<M: for<'a> Deserialize<'a> + Clone + 'static>(
     url: &str,
+    poll_url: Option<&str>,
+) -> UnboundedReceiver<M> {
+    spawn_websocket_stream(
+        url.to_string(),
+        poll_url.map(str::to_string),
+        |url: &str| url.to_string(),
+        |_: &M| (),
+    )
+}
+
+/// A stream that reconnects from the last sequence received,
+/// instead of downloading everything again.
+pub fn create_resumable_stream<M: for<'a> Deserialize<'a> + Clone + 'static>(
+    url: &str,
+    poll_url: &str,
+) -> UnboundedReceiver<Sequenced<M>> {
+    let last_seq = Rc::new(Cell::new(None));
+    let received = last_seq.clone();
+
+    spawn_websocket_stream(
+        url.to_string(),
+        Some(poll_url.to_string()),
+        move |url: &str| match last_seq.get() {
+            Some(seq) => {
+                let separator = if url.contains('?') { '&' } else { '?' };
+                format!("{url}{separator}since={seq}")
+            }
+            None => url.to_string(),
+        },
+        move |message: &Sequenced<M>| received.set(Some(message.seq)),
+    )
+}
+
+async fn forward<M>(tx: &mut UnboundedSender<M>, on_message: &impl Fn(&M), message: M) {
+    on_message(&message);
+    if let Err(err) = tx.send(message).await {
+        console_error(&format!("unbounded channel timeout: {err:?}"));
+    }
+}
+
+fn spawn_websocket_stream<M: for<'a> Deserialize<'a> + Clone + 'static>(
+    ws_url: String,
+    poll_url: Option<String>,
+    resume: impl Fn(&str) -> String + 'static,
+    on_message: impl Fn(&M) + 'static,
 ) -> UnboundedReceiver<M> {
-    let url = url.to_string();
     let (mut tx, rx) = mpsc::unbounded::<M>();
 
     spawn_local(async move {
-        loop {
+        let mut failures = 0;
+        while poll_url.is_none() || failures < WEBSOCKET_ATTEMPTS {
+            let url = resume(&ws_url);
+            let mut received = false;
             match WebSocket::open(&url) {
                 Ok(ws) => {
                     console_log(&format!("ws connected: {url}"));
                     let (_, mut read) = ws.split();
                     loop {
                         match parse_message::<M>(read.next().await) {
-                            Ok(next_timer) => {
-                                if let Err(err) = tx.send(next_timer).await {
-                                    console_error(&format!("unbounded channel timeout: {err:?}"));
-                                }
+                            Ok(message) => {
+                                received = true;
+                                forward(&mut tx, &on_message, message).await;
                             }
                             Err(err) => {
                                 match err {
-                                    Error::Serde(err) => {
+                                    Error::Decode(err) => {
                                         console_error(&format!("failed parsing response: {err:?}"))
                                     }
                                     Error::WebSocket(err) => {
@@ -63,9 +125,29 @@ pub fn create_websocket_stream<M: for<'a> Deserialize<'a> + Clone + 'static>(
                 }
                 Err(err) => console_error(&format!("Websocket error: {:?}", err)),
             }
+            failures = if received { 0 } else { failures + 1 };
             console_log("Wait 5 seconds to reconnect.");
             TimeoutFuture::new(5_000).await;
         }
+
+        if let Some(poll_url) = poll_url {
+            console_warn(&format!("websockets keep failing, polling: {poll_url}"));
+            loop {
+                // The server holds the poll until there is something new.
+                match get_url::<Vec<M>>(&resume(&poll_url)).await {
+                    Ok(messages) => {
+                        for message in messages {
+                            forward(&mut tx, &on_message, message).await;
+                        }
+                    }
+                    Err(err) => {
+                        console_error(&format!("failed polling: {err:?}"));
+                        console_log("Wait 5 seconds to poll again.");
+                        TimeoutFuture::new(5_000).await;
+                    }
+                }
+            }
+        }
     });
 
     rx
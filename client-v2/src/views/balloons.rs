@@ -0,0 +1,94 @@
+use std::time::Duration;
+
+use data::balloons::Balloon;
+use leptos::{logging::*, prelude::*, task::spawn_local};
+
+use crate::api::{create_balloons, set_balloon_delivered};
+
+use super::metadata::use_problem_metadata;
+
+#[component]
+fn BalloonItem(balloon: Balloon, toggle: Callback<(i64, bool)>) -> impl IntoView {
+    let color = use_problem_metadata(&balloon.problem).and_then(|m| m.color);
+    let style = color.map(|color| format!("--balloon-color: {color}"));
+    let img = if style.is_some() {
+        "balloon-img"
+    } else {
+        "accept-img"
+    };
+    let run_id = balloon.run_id;
+    let delivered = balloon.delivered;
+
+    view! {
+        <div class="balloonitem" class:first=balloon.is_first>
+            <div class="balloonproblem">
+                <div class=img style=style></div>
+                <span>{balloon.problem.to_string()}</span>
+            </div>
+            <div class="balloonteam">
+                {balloon.is_first.then_some("★ ")} {balloon.team_name} " ("
+                {balloon.team_login} ")"
+            </div>
+            <div class="balloontime">{balloon.time}</div>
+            <button on:click=move |_| {
+                toggle.run((run_id, !delivered))
+            }>{if delivered { "Desfazer" } else { "Entregue" }}</button>
+        </div>
+    }
+}
+
+/// Balloons of the sede of the secret, for the staff delivering them.
+#[component]
+pub fn Balloons(secret: String, contest: Option<String>) -> impl IntoView {
+    let balloons = RwSignal::new(Vec::<Balloon>::new());
+
+    let (poll_secret, poll_contest) = (secret.clone(), contest.clone());
+    let poll = move || {
+        let (secret, contest) = (poll_secret.clone(), poll_contest.clone());
+        spawn_local(async move {
+            let fresh = create_balloons(secret, contest).await;
+            // The queue may be gone by the time the request finishes.
+            balloons.try_set(fresh);
+        });
+    };
+    poll();
+    match set_interval_with_handle(poll, Duration::from_secs(5)) {
+        Ok(handle) => on_cleanup(move || handle.clear()),
+        Err(err) => error!("failed polling balloons: {err:?}"),
+    }
+
+    let toggle = Callback::new(move |(run_id, delivered): (i64, bool)| {
+        balloons.update(|balloons| {
+            for balloon in balloons.iter_mut().filter(|b| b.run_id == run_id) {
+                balloon.delivered = delivered;
+            }
+        });
+
+        let (secret, contest) = (secret.clone(), contest.clone());
+        spawn_local(async move {
+            if let Err(err) = set_balloon_delivered(run_id, delivered, secret, contest).await {
+                error!("failed updating balloon {run_id}: {err:?}");
+            }
+        });
+    });
+
+    let list = move |delivered: bool| {
+        move || {
+            balloons
+                .get()
+                .into_iter()
+                .filter(|b| b.delivered == delivered)
+                .map(|balloon| view! { <BalloonItem balloon toggle /> })
+                .collect_view()
+        }
+    };
+
+    view! {
+        <div class="balloonqueue">
+            <h2>"Balões pendentes"</h2>
+            {list(false)}
+            <h2>"Balões entregues"</h2>
+            {list(true)}
+        </div>
+    }
+}
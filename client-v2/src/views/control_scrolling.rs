@@ -1,22 +1,35 @@
+use std::{cell::Cell, rc::Rc};
+
 use codee::string::FromToStringCodec;
-use data::remote_control::{ControlMessage, QueryString, WindowScroll};
-use leptos::{logging::error, prelude::*};
+use data::remote_control::{ControlMessage, QueryString, SequencedControl, WindowScroll};
+use gloo_timers::future::TimeoutFuture;
+use leptos::{
+    logging::{error, warn},
+    prelude::*,
+    task::spawn_local,
+};
 use leptos_router::{
     hooks::{use_navigate, use_query, use_query_map},
     params::Params,
 };
 use leptos_use::{
-    signal_throttled, use_idle, use_websocket, use_window_scroll, UseIdleReturn, UseWebSocketReturn,
+    core::ConnectionReadyState, signal_throttled, use_idle, use_websocket, use_window_scroll,
+    UseIdleReturn, UseWebSocketReturn,
 };
 use web_sys::ScrollToOptions;
 
-use crate::api::remote_control_url;
+use crate::{
+    api::{remote_control_poll_url, remote_control_url},
+    net::request_signal::get_url,
+};
 
 use super::team_media::{use_global_photo_state, PhotoState};
 
 #[derive(PartialEq, Eq, Clone, Default)]
 struct RemoteControlQuery {
     remote_control: Option<String>,
+    /// Operator token, needed to send commands when the server requires one.
+    token: Option<String>,
 }
 
 impl Params for RemoteControlQuery {
@@ -24,7 +37,11 @@ impl Params for RemoteControlQuery {
         map: &leptos_router::params::ParamsMap,
     ) -> std::result::Result<Self, leptos_router::params::ParamsError> {
         let remote_control = map.get("remote_control");
-        Ok(RemoteControlQuery { remote_control })
+        let token = map.get("token");
+        Ok(RemoteControlQuery {
+            remote_control,
+            token,
+        })
     }
 }
 
@@ -130,6 +147,52 @@ fn Scrolling<SendFn: Fn(&String) + 'static>(idle: Signal<bool>, send: SendFn) ->
     });
 }
 
+/// Follows the remote control by polling while its websocket is closed, e.g. by a proxy.
+/// Polling screens only follow the others, they cannot send.
+fn poll_while_closed(
+    key: String,
+    ready_state: Signal<ConnectionReadyState>,
+) -> ReadSignal<Option<ControlMessage>> {
+    let (polled, set_polled) = signal(None);
+    let polling = Rc::new(Cell::new(false));
+
+    Effect::new(move |_| {
+        if ready_state.get() != ConnectionReadyState::Closed || polling.get() {
+            return;
+        }
+        polling.set(true);
+
+        let key = key.clone();
+        let polling = polling.clone();
+        spawn_local(async move {
+            warn!("remote control websocket closed, polling: {key}");
+            let mut since = None;
+            // Stops once the websocket is open again, or the screen stops following.
+            while ready_state
+                .try_get_untracked()
+                .is_some_and(|state| state != ConnectionReadyState::Open)
+            {
+                let url = remote_control_poll_url(&key, since);
+                match get_url::<Vec<SequencedControl>>(&url).await {
+                    Ok(messages) => {
+                        if let Some(last) = messages.into_iter().last() {
+                            since = Some(last.seq);
+                            set_polled.try_set(Some(last.message));
+                        }
+                    }
+                    Err(err) => {
+                        error!("failed polling remote control: {err:?}");
+                        TimeoutFuture::new(5_000).await;
+                    }
+                }
+            }
+            polling.set(false);
+        });
+    });
+
+    polled
+}
+
 #[component]
 pub fn RemoteControl() -> impl IntoView {
     let query = use_query::<RemoteControlQuery>();
@@ -140,16 +203,25 @@ pub fn RemoteControl() -> impl IntoView {
         query
             .get()
             .ok()
-            .and_then(|key| key.remote_control)
-            .map(|key| {
-                let UseWebSocketReturn { message, send, .. } =
-                    use_websocket::<String, String, FromToStringCodec>(&remote_control_url(&key));
+            .and_then(|query| query.remote_control.map(|key| (key, query.token)))
+            .map(|(key, token)| {
+                let UseWebSocketReturn {
+                    message,
+                    send,
+                    ready_state,
+                    ..
+                } = use_websocket::<String, String, FromToStringCodec>(&remote_control_url(
+                    &key,
+                    token.as_deref(),
+                ));
                 let UseIdleReturn { idle, .. } = use_idle(5_000);
+                let polled = poll_while_closed(key, ready_state);
 
-                let message_signal = Memo::new(move |_| {
-                    message
+                let message_signal = Memo::new(move |_| match ready_state.get() {
+                    ConnectionReadyState::Open => message
                         .get()
-                        .and_then(|text| serde_json::from_str::<ControlMessage>(&text).ok())
+                        .and_then(|text| serde_json::from_str::<ControlMessage>(&text).ok()),
+                    _ => polled.get(),
                 });
 
                 view! {
@@ -0,0 +1,26 @@
+use std::sync::Arc;
+
+use data::{
+    configdata::{Metadata, ProblemMetadata, TeamMetadata},
+    Letter,
+};
+use leptos::prelude::*;
+
+#[derive(Clone)]
+struct MetadataContext {
+    metadata: Arc<Metadata>,
+}
+
+pub fn provide_metadata(metadata: Option<Metadata>) {
+    provide_context(MetadataContext {
+        metadata: Arc::new(metadata.unwrap_or_default()),
+    })
+}
+
+pub fn use_team_metadata(login: &str) -> Option<TeamMetadata> {
+    use_context::<MetadataContext>().and_then(|c| c.metadata.team(login).cloned())
+}
+
+pub fn use_problem_metadata(letter: &Letter) -> Option<ProblemMetadata> {
+    use_context::<MetadataContext>().and_then(|c| c.metadata.problem(letter).cloned())
+}
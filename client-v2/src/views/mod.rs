@@ -1,7 +1,9 @@
 mod background_color;
+mod balloons;
 mod compress_placements;
 mod contest;
 mod control_scrolling;
+mod metadata;
 mod navigation;
 mod placement;
 mod problem;
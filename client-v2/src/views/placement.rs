@@ -7,7 +7,7 @@ pub fn get_color(n: usize, sede: &Sede) -> Option<Color> {
     sede.premio(n)
 }
 
-fn get_class(color: Color) -> &'static str {
+pub fn get_class(color: Color) -> &'static str {
     match color {
         Color::Red => "vermelho",
         Color::Gold => "ouro",
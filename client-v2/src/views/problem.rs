@@ -1,6 +1,8 @@
 use data::Letter;
 use leptos::prelude::*;
 
+use super::metadata::use_problem_metadata;
+
 fn number_submissions(s: usize) -> Option<usize> {
     if s == 1 {
         None
@@ -11,6 +13,10 @@ fn number_submissions(s: usize) -> Option<usize> {
 
 #[component]
 pub fn Problem(prob: Letter, problem: Signal<Option<data::ProblemView>>) -> impl IntoView {
+    let metadata = use_problem_metadata(&prob).unwrap_or_default();
+    let title = metadata.title;
+    let color = metadata.color;
+
     let problem_class = move || {
         problem.with(|p| match &p {
             Some(p) => {
@@ -31,13 +37,16 @@ pub fn Problem(prob: Letter, problem: Signal<Option<data::ProblemView>>) -> impl
         problem.with(|problem| match problem {
             Some(p) => (if p.solved {
                 let balao = format!("balao_{}", prob);
-                let img = if p.solved_first {
-                    "star-img"
-                } else {
-                    "accept-img"
+                let img = match (&color, p.solved_first) {
+                    (_, true) => "star-img",
+                    (Some(_), false) => "balloon-img",
+                    (None, false) => "accept-img",
                 };
+                let style = color
+                    .as_ref()
+                    .map(|color| format!("--balloon-color: {color}"));
                 view! {
-                    <div class=format!("{img} {balao}")></div>
+                    <div class=format!("{img} {balao}") style=style></div>
                     <div class="accept-text cell-content">
                         <div class="cima">
                             +{number_submissions(p.submissions)}
@@ -67,7 +76,7 @@ pub fn Problem(prob: Letter, problem: Signal<Option<data::ProblemView>>) -> impl
     };
 
     view! {
-        <div class={problem_class}>
+        <div class={problem_class} title=title>
             {problem_content}
         </div>
     }
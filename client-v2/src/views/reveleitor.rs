@@ -1,178 +1,224 @@
 use std::{collections::HashMap, sync::Arc, sync::Mutex};
 
-use data::{configdata::Sede, revelation::RevelationDriver, ContestFile, RunsFile};
+use codee::string::FromToStringCodec;
+use data::{
+    configdata::Sede,
+    revelation::{
+        AwardMoment, RevelationCommand, RevelationDelta, RevelationState, RevelationUpdate,
+        RevelationView,
+    },
+    ContestFile, RunsFile,
+};
 use leptos::{ev, logging::*, prelude::*};
 use leptos_router::hooks::use_query_map;
+use leptos_use::{use_websocket, UseWebSocketReturn};
 
 use crate::{
-    api::create_secret_runs, model::contest_signal::ContestSignal, views::contest::ContestPanel,
+    api::{create_secret_runs, revelation_session_url},
+    model::contest_signal::ContestSignal,
+    views::{contest::ContestPanel, placement::get_class},
 };
 
-#[derive(Debug)]
-pub struct State {
-    is_started: bool,
-    driver: RevelationDriver,
-}
-
-impl State {
-    fn new(contest: ContestFile, runs: RunsFile, sede: &Sede) -> Self {
-        let sub_contest = contest.filter_sede(sede);
-        Self {
-            is_started: false,
-            driver: RevelationDriver::new(sub_contest, runs),
-        }
-    }
-
-    fn step_forward(&mut self) {
-        if self.is_started && !self.driver.is_empty() {
-            self.driver.reveal_step();
-        } else {
-            self.is_started = true;
-        }
-    }
-
-    fn jump_team_forward(&mut self) {
-        if self.is_started {
-            self.driver.jump_team_forward();
-        } else {
-            self.is_started = true
-        }
-    }
-
-    fn jump_team_back(&mut self) {
-        let n = self.driver.len();
-        self.reveal_top_n(n + 1)
-    }
-
-    fn step_back(&mut self) {
-        self.is_started = true;
-        self.driver.back_one();
-    }
-
-    fn reveal_top_n(&mut self, n: usize) {
-        self.is_started = true;
-        self.driver.restart();
-        self.driver
-            .reveal_top_n(n)
-            .inspect_err(|err| error!("failed step: {err:?}"))
-            .ok();
-    }
-
-    fn reveal_all(&mut self) {
-        self.driver
-            .reveal_top_n(0)
-            .inspect_err(|err| error!("failed step: {err:?}"))
-            .ok();
-        self.is_started = false;
-    }
-
-    fn reset(&mut self) {
-        self.is_started = false;
-        self.driver.restart();
-    }
-}
-
 #[component]
 pub fn RevelationPanel(
     original_contest: Arc<ContestFile>,
-    state: ReadSignal<State>,
+    center: Signal<Option<String>>,
     contest_signal: Arc<ContestSignal>,
     sede: Signal<Arc<Sede>>,
 ) -> impl IntoView {
-    let center = Signal::derive(move || {
-        state
-            .with(|state| state.is_started.then_some(state.driver.peek().cloned()))
-            .flatten()
-    });
-
     view! { <ContestPanel original_contest contest_signal center titulo=None.into() sede /> }
 }
 
 #[component]
-pub fn Control(state: WriteSignal<State>) -> impl IntoView {
+pub fn Control<SendFn: Fn(RevelationCommand) + Clone + 'static>(send: SendFn) -> impl IntoView {
+    let keydown = send.clone();
     let handle = window_event_listener(ev::keydown, move |ev| match ev.code().as_str() {
-        "ArrowLeft" => state.update(|d| d.step_back()),
-        "ArrowRight" => state.update(|d| d.step_forward()),
-        "ArrowUp" => state.update(|d| d.jump_team_forward()),
-        "ArrowDown" => state.update(|d| d.jump_team_back()),
-        "Backspace" => state.update(|d| d.reset()),
+        "ArrowLeft" => keydown(RevelationCommand::StepBack),
+        "ArrowRight" => keydown(RevelationCommand::StepForward),
+        "ArrowUp" => keydown(RevelationCommand::JumpTeamForward),
+        "ArrowDown" => keydown(RevelationCommand::JumpTeamBack),
+        "Backspace" => keydown(RevelationCommand::Reset),
+        "KeyM" => keydown(RevelationCommand::NextAward),
         code => log!("ev code: {code}"),
     });
     on_cleanup(move || handle.remove());
+
+    let buttons = [
+        ("←", RevelationCommand::StepBack),
+        ("→", RevelationCommand::StepForward),
+        ("↑", RevelationCommand::JumpTeamForward),
+        ("↓", RevelationCommand::JumpTeamBack),
+        ("Top 100", RevelationCommand::TopN(100)),
+        ("Top 50", RevelationCommand::TopN(50)),
+        ("Top 30", RevelationCommand::TopN(30)),
+        ("Top 10", RevelationCommand::TopN(10)),
+        ("Next award", RevelationCommand::NextAward),
+        ("All", RevelationCommand::RevealAll),
+        ("Reset", RevelationCommand::Reset),
+    ];
+
     view! {
         <div class="commandpanel">
-            <button on:click=move |_| { state.update(|d| d.step_back())}>
-                {"←"}
-            </button>
-            <button on:click=move |_| { state.update(|d| d.step_forward())}>
-                {"→"}
-            </button>
-            <button on:click=move |_| { state.update(|d| d.jump_team_forward())}>
-                {"↑"}
-            </button>
-            <button on:click=move |_| { state.update(|d| d.jump_team_back())}>
-                {"↓"}
-            </button>
-            <button on:click=move |_| { state.update(|d| d.reveal_top_n(100))}>
-                Top 100
-            </button>
-            <button on:click=move |_| { state.update(|d| d.reveal_top_n(50))}>
-                Top 50
-            </button>
-            <button on:click=move |_| { state.update(|d| d.reveal_top_n(30))}>
-                Top 30
-            </button>
-            <button on:click=move |_| { state.update(|d| d.reveal_top_n(10))}>
-                Top 10
-            </button>
-            <button on:click=move |_| { state.update(|d| d.reveal_all())}>
-                All
-            </button>
-            <button on:click=move |_| { state.update(|d| d.reset())}>
-                Reset
-            </button>
+            {buttons
+                .into_iter()
+                .map(|(label, command)| {
+                    let send = send.clone();
+                    view! { <button on:click=move |_| send(command)>{label}</button> }
+                })
+                .collect_view()}
         </div>
     }
 }
 
+/// Shown when the revelation stops at a medal cut-off.
+#[component]
+pub fn AwardBanner(award: Signal<Option<AwardMoment>>) -> impl IntoView {
+    move || {
+        award.get().map(|AwardMoment { medal, placement }| {
+            view! {
+                <div class=format!("awardbanner {}", get_class(medal))>
+                    {format!("Top {placement}")}
+                </div>
+            }
+        })
+    }
+}
+
+/// Updates the signals of the teams that changed since the last revelation step.
+fn update_contest_signal(
+    contest_signal: &ContestSignal,
+    team_ids: &Mutex<HashMap<String, u64>>,
+    contest: &ContestFile,
+) {
+    let mut id_map = team_ids.lock().unwrap();
+
+    let mut changed_logins = vec![];
+    for team in contest.teams.values() {
+        let id_changed = id_map.get(&team.login).is_none_or(|id| &team.id != id);
+
+        if id_changed {
+            changed_logins.push(team.login.as_str());
+            id_map.insert(team.login.clone(), team.id);
+        }
+    }
+
+    contest_signal.update(changed_logins.into_iter(), contest)
+}
+
 #[component]
 pub fn Revelation(sede: Arc<Sede>, runs_file: RunsFile, contest: ContestFile) -> impl IntoView {
     log!("revelation");
     let contest_signal = Arc::new(ContestSignal::new(&contest));
-    let contest = contest.clone();
     let original_contest = Arc::new(contest.clone());
-    let driver = State::new(contest, runs_file, &sede);
+    let state = RevelationState::new(contest.filter_sede(&sede), runs_file, &sede);
     let (get_sede, _) = signal(sede.clone());
 
-    let (get_driver, set_driver) = signal(driver);
+    let (get_state, set_state) = signal(state);
 
     let effect_contest_signal = contest_signal.clone();
 
     let team_ids = Arc::new(Mutex::new(HashMap::new()));
 
     Effect::new(move |_| {
-        get_driver.with(|state| {
-            let contest = state.driver.contest();
-            let mut id_map = team_ids.lock().unwrap();
-
-            let mut changed_logins = vec![];
-            for team in contest.teams.values() {
-                let id_changed = id_map.get(&team.login).is_none_or(|id| &team.id != id);
-
-                if id_changed {
-                    changed_logins.push(team.login.as_str());
-                    id_map.insert(team.login.clone(), team.id);
-                }
-            }
-
-            effect_contest_signal.update(changed_logins.into_iter(), contest)
+        get_state.with(|state| {
+            update_contest_signal(&effect_contest_signal, &team_ids, state.contest())
         });
     });
 
+    let center = Signal::derive(move || get_state.with(|state| state.center().cloned()));
+    let award = Signal::derive(move || get_state.with(|state| state.award_moment().cloned()));
+
+    let send = move |command| {
+        set_state.update(|state| {
+            state
+                .apply(command)
+                .inspect_err(|err| error!("failed step: {err:?}"))
+                .ok();
+        })
+    };
+
     view! {
-        <Control state=set_driver />
+        <Control send />
+        <AwardBanner award />
         <div class="revelationpanel">
-            <RevelationPanel original_contest contest_signal state=get_driver sede=get_sede.into() />
+            <RevelationPanel original_contest contest_signal center sede=get_sede.into() />
+        </div>
+    }
+}
+
+/// A revelation hosted by the server.
+/// Every screen connected to the same session shows the same step.
+#[component]
+pub fn SessionRevelation(sede: Arc<Sede>, url: String, contest: ContestFile) -> impl IntoView {
+    log!("session revelation");
+    let contest_signal = Arc::new(ContestSignal::new(&contest));
+    let original_contest = Arc::new(contest.clone());
+    let (get_sede, _) = signal(sede);
+    // The contest as of the last step, patched by the teams of each delta.
+    let current = Mutex::new(contest);
+
+    let UseWebSocketReturn { message, send, .. } =
+        use_websocket::<String, String, FromToStringCodec>(&url);
+
+    let (center, set_center) = signal(None);
+    let (award, set_award) = signal(None);
+
+    let effect_contest_signal = contest_signal.clone();
+
+    let team_ids = Arc::new(Mutex::new(HashMap::new()));
+
+    Effect::new(move |_| {
+        message.with(|message| {
+            if let Some(text) = message {
+                let (center, award) = match serde_json::from_str::<RevelationUpdate>(text) {
+                    Ok(RevelationUpdate::Full(RevelationView {
+                        step: _,
+                        center,
+                        award,
+                        contest,
+                    })) => {
+                        *current.lock().unwrap() = contest;
+                        (center, award)
+                    }
+                    Ok(RevelationUpdate::Delta(RevelationDelta {
+                        step: _,
+                        center,
+                        award,
+                        teams,
+                    })) => {
+                        let mut current = current.lock().unwrap();
+                        for team in teams {
+                            current.teams.insert(team.login.clone(), team);
+                        }
+                        (center, award)
+                    }
+                    Err(err) => {
+                        error!("failed parsing revelation view: {err:?}");
+                        return;
+                    }
+                };
+                update_contest_signal(&effect_contest_signal, &team_ids, &current.lock().unwrap());
+                set_center.set(center);
+                set_award.set(award);
+            }
+        })
+    });
+
+    let send = move |command| match serde_json::to_string(&command) {
+        Ok(text) => send(&text),
+        Err(err) => error!("failed serializing revelation command: {err:?}"),
+    };
+
+    view! {
+        <Control send />
+        <AwardBanner award=award.into() />
+        <div class="revelationpanel">
+            <RevelationPanel
+                original_contest
+                contest_signal
+                center=center.into()
+                sede=get_sede.into()
+            />
         </div>
     }
 }
@@ -181,6 +227,15 @@ pub fn Revelation(sede: Arc<Sede>, runs_file: RunsFile, contest: ContestFile) ->
 pub fn Reveleitor(sede: Arc<Sede>, secret: String, contest: Arc<ContestFile>) -> impl IntoView {
     log!("reveleitor");
     let query_map = use_query_map();
+    let contest = ContestFile::clone(&contest);
+
+    let session = query_map.with_untracked(|q| q.get("session"));
+    if let Some(session) = session {
+        let contest_name = query_map.with_untracked(|q| q.get("contest"));
+        let url = revelation_session_url(&session, &secret, contest_name);
+        return view! { <SessionRevelation sede url contest /> }.into_any();
+    }
+
     let all_runs = LocalResource::new(move || {
         log!("fetching secret runs");
         let secret = secret.clone();
@@ -188,10 +243,10 @@ pub fn Reveleitor(sede: Arc<Sede>, secret: String, contest: Arc<ContestFile>) ->
         create_secret_runs(secret, contest_name)
     });
 
-    let contest = ContestFile::clone(&contest);
     Suspend::new(async move {
         let runs_file = all_runs.await;
 
         view! { <Revelation sede=sede.clone() runs_file contest /> }
     })
+    .into_any()
 }
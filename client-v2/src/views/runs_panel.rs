@@ -39,7 +39,7 @@ impl Compress for ItemWrap {
                 placement,
                 escola,
                 team_name,
-                team_login: _,
+                team_login,
                 order: _,
                 problem,
                 problem_view,
@@ -56,7 +56,7 @@ impl Compress for ItemWrap {
                 <div class="run_box" style:top={top} style:z-index={z_index}>
                     <div class="run">
                         <Placement placement=placement sede />
-                        <TeamName escola={escola.clone()} name={team_name.clone()} />
+                        <TeamName login={team_login.clone()} escola={escola.clone()} name={team_name.clone()} />
                         <div class="cell quadrado">{problem.to_string()}</div>
                         <Problem prob=problem problem=Signal::derive(move || Some(problem_view.clone())) />
                     </div>
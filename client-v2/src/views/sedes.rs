@@ -4,7 +4,8 @@ use data::{
     configdata::{ConfigContest, Sede},
     ContestFile, TimerData,
 };
-use leptos::prelude::*;
+use futures::StreamExt;
+use leptos::{logging::log, prelude::*, task::spawn_local};
 use leptos_router::{
     components::{Route, Router, Routes},
     hooks::use_query,
@@ -13,7 +14,9 @@ use leptos_router::{
 };
 
 use crate::{
-    api::{create_config, create_timer, ContestQuery},
+    api::{
+        create_config, create_config_updates, create_timer, location_contest_query, ContestQuery,
+    },
     model::{
         contest_signal::ContestSignal, provide_contest, runs_panel_signal::RunsPanelItemManager,
         ContestProvider,
@@ -23,11 +26,12 @@ use crate::{
         contest::Contest,
         control_scrolling::RemoteControl,
         global_settings::{use_global_settings, SettingsPanel},
+        metadata::provide_metadata,
         navigation::Navigation,
     },
 };
 
-use super::{reveleitor::Reveleitor, timer::Timer};
+use super::{balloons::Balloons, reveleitor::Reveleitor, timer::Timer};
 
 trait IsNegative {
     fn is_negative(&self) -> bool;
@@ -44,6 +48,7 @@ struct QueryParams {
     sede: Option<String>,
     secret: Option<String>,
     settings: Option<bool>,
+    balloons: Option<bool>,
 }
 
 impl Params for QueryParams {
@@ -51,10 +56,12 @@ impl Params for QueryParams {
         let sede = map.get("sede");
         let secret = map.get("secret");
         let settings = map.get("settings").and_then(|s| s.parse::<bool>().ok());
+        let balloons = map.get("balloons").and_then(|s| s.parse::<bool>().ok());
         Ok(QueryParams {
             sede,
             secret,
             settings,
+            balloons,
         })
     }
 }
@@ -63,6 +70,10 @@ impl QueryParams {
     fn is_settings_enabled(&self) -> bool {
         self.settings.unwrap_or_default()
     }
+
+    fn is_balloons_enabled(&self) -> bool {
+        self.balloons.unwrap_or_default()
+    }
 }
 
 fn use_static_query() -> Signal<QueryParams> {
@@ -98,6 +109,7 @@ fn ProvideSede(
     timer: ReadSignal<(TimerData, TimerData)>,
     sede_param: Signal<QueryParams>,
 ) -> impl IntoView {
+    provide_metadata(config_contest.metadata.clone());
     let titulo = use_titulo(config_contest.clone());
     let titulo_sede = titulo.clone();
     let sede = Memo::new(move |_| {
@@ -132,6 +144,7 @@ fn ConfiguredReveleitor(
 
     Suspend::new(async move {
         let provider = contest_provider.await;
+        provide_metadata(provider.config_contest.metadata.clone());
         let titulo = use_titulo(provider.config_contest.clone());
         let sede = use_configured_sede(provider.config_contest.clone(), titulo, sede_param);
 
@@ -141,9 +154,39 @@ fn ConfiguredReveleitor(
     })
 }
 
+#[component]
+fn ConfiguredBalloons(
+    contest_provider: LocalResource<ContestProvider>,
+    secret: String,
+    contest: Option<String>,
+) -> impl IntoView {
+    Suspend::new(async move {
+        let provider = contest_provider.await;
+        provide_metadata(provider.config_contest.metadata.clone());
+
+        view! { <Balloons secret=secret.clone() contest=contest.clone() /> }
+    })
+}
+
+/// Changes every time the server reloads its config.
+fn create_config_version() -> ReadSignal<u64> {
+    let mut updates = create_config_updates();
+    let (version, set_version) = signal(0);
+
+    spawn_local(async move {
+        while let Some(next) = updates.next().await {
+            log!("config reloaded: {next}");
+            set_version.set(next);
+        }
+    });
+
+    version
+}
+
 #[component]
 pub fn Sedes() -> impl IntoView {
-    let timer = create_timer();
+    let timer = create_timer(location_contest_query());
+    let config_version = create_config_version();
 
     let negative_memo = Memo::new(move |_| timer.get().is_negative());
 
@@ -171,16 +214,24 @@ pub fn Sedes() -> impl IntoView {
         let animeitor = move || {
             let contest_provider = LocalResource::new(move || {
                 let q = contest_query.get();
+                // The scoreboard follows config reloads, a revelation keeps its state.
+                if secret.get_untracked().is_none() {
+                    config_version.track();
+                }
                 provide_contest(q)
             });
 
             match secret.get() {
+                Some(secret) if query_params.with(|q| q.is_balloons_enabled()) => view! {
+                    <ConfiguredBalloons contest_provider secret contest=contest_query.get().contest />
+                }.into_any(),
                 Some(secret) => (move || view! {
                     <ConfiguredReveleitor contest_provider=contest_provider secret=secret.clone() sede_param=query_params.with(|p| p.sede.clone()) />
                 }).into_any(),
                 None => {
                     let config_contest = LocalResource::new(move || {
                         let q = contest_query.get();
+                        config_version.track();
                         create_config(q)
                     });
                     let suspend = Suspend::new(async move {
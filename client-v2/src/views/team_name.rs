@@ -1,11 +1,20 @@
 use leptos::prelude::*;
 
+use super::metadata::use_team_metadata;
+
 #[component]
-pub fn TeamName(escola: String, name: String) -> impl IntoView {
+pub fn TeamName(login: String, escola: String, name: String) -> impl IntoView {
+    let metadata = use_team_metadata(&login).unwrap_or_default();
+    let flag = metadata.flag();
+    let escola = metadata.short_school.clone().unwrap_or(escola);
+    let logo = metadata
+        .logo
+        .map(|logo| view! { <img class="logoEscola" src=logo /> });
+
     let is_long = name.len() > 30;
     view! {
         <div class="cell time">
-            <div class:nomeEscola=true >{escola}</div>
+            <div class:nomeEscola=true >{logo}{flag.map(|flag| format!("{flag} "))}{escola}</div>
             <div class:nomeTime=true class:longTeamName=is_long >{name}</div>
         </div>
     }
@@ -16,6 +16,7 @@ pub fn TeamScoreLine(
     local_placement: Signal<Option<usize>>,
     sede: Signal<Arc<Sede>>,
 ) -> impl IntoView {
+    let login = team.login.clone();
     let escola = team.escola.clone();
     let name = team.name.clone();
     let score = team.score;
@@ -44,7 +45,7 @@ pub fn TeamScoreLine(
                 {move || local_placement.get().map(|placement|
                     view!{ <Placement placement sede /> }
                 )}
-                <TeamName escola name />
+                <TeamName login escola name />
                 <div class="cell problema quadrado">
                     <div class="cima">{move || score.with(|s| s.solved)}</div>
                     <div class="baixo">{move || score.with(|s| s.penalty)}</div>
@@ -38,6 +38,7 @@ pub fn Timer(timer: ReadSignal<(TimerData, TimerData)>) -> impl IntoView {
                 <span class="sep"> ":" </span>
                 <span class={["segundo", changed(seg(time), seg(ptime))].join(" ")}>{ f(seg(time_data.current_time))} </span>
             </div>
+            {time_data.stale.then(|| view! { <div class="stale">"Scoreboard out of date, waiting for the contest data"</div> })}
         }
     }
 }
@@ -42,17 +42,16 @@ fn print_sede(
 fn print_reveleitor(
     parse: &SimpleParser,
     sede: &Sede,
-    secret: &str,
     contest_name: &str,
 ) -> color_eyre::eyre::Result<()> {
     let mut url = Url::parse(&parse.prefix)?;
     url.query_pairs_mut()
-        .append_pair("secret", secret)
         .append_pair("sede", &sede.entry.name)
         .append_pair("contest", contest_name);
 
+    // Only the hashes of the secrets are kept, regenerate_secrets prints the secrets.
     println!("-> {}", sede.entry.name);
-    println!("    Reveleitor em {}", url.as_str());
+    println!("    Reveleitor em {}&secret=<secret>", url.as_str());
     if parse.filters {
         println!("    Filters = {:?}", sede.entry.codes);
     }
@@ -71,8 +70,8 @@ fn print_urls(
     //     print_sede(parse, sede, contest_name)?;
     // }
 
-    for (secret, sede) in &config_secret.sedes_by_secret {
-        print_reveleitor(parse, sede, secret, contest_name)?;
+    for sede in config_secret.sedes() {
+        print_reveleitor(parse, sede, contest_name)?;
     }
     Ok(())
 }
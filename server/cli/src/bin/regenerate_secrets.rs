@@ -1,29 +1,64 @@
+use clap::Parser;
 use data::configdata::*;
 
-use rand::{Rng, distr::Alphanumeric}; // 0.8
+use rand::{Rng, distr::Alphanumeric};
+
+#[derive(Parser)]
+#[command(version, about, long_about = None)]
+/// Regenerates the secrets and tokens of secrets files.
+///
+/// The files, with only the hashes, are printed to stdout.
+/// The secrets and tokens are printed to stderr, and are not kept anywhere else.
+struct Args {
+    /// Secrets files.
+    files: Vec<String>,
+
+    #[clap(long)]
+    /// Hours until the new secrets and tokens expire.
+    valid_for_hours: Option<u64>,
+}
 
 fn secret() -> String {
     rand::rng()
         .sample_iter(&Alphanumeric)
-        .take(4)
+        .take(32)
         .map(char::from)
         .collect()
 }
 
-fn shuffle_secrets(secrets: &mut ConfigSecret) {
+fn shuffle_secrets(secrets: &mut ConfigSecret, expires: Option<u64>) {
     for sede in &mut secrets.secrets {
-        sede.secret = secret();
+        let secret = secret();
+        eprintln!("secret of {}: {secret}", sede.name);
+        sede.secret = None;
+        sede.hash = Some(hash_token(&secret));
+        sede.expires = expires;
+    }
+
+    for token in &mut secrets.tokens {
+        let secret = secret();
+        eprintln!("token of {} ({:?}): {secret}", token.name, token.role);
+        token.hash = hash_token(&secret);
+        token.expires = expires;
     }
 }
 
 fn main() -> color_eyre::eyre::Result<()> {
-    let args: Vec<String> = std::env::args().collect();
+    let args = Args::parse();
+
+    let expires = args.valid_for_hours.map(|hours| {
+        std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or_default()
+            + hours * 3600
+    });
 
-    for a in args.iter().skip(1) {
+    for a in &args.files {
         let f = std::fs::read_to_string(a)?;
         let mut secrets: ConfigSecret = toml::from_str(&f)?;
-        shuffle_secrets(&mut secrets);
-        let result = toml::to_string(&secrets).unwrap();
+        shuffle_secrets(&mut secrets, expires);
+        let result = toml::to_string(&secrets)?;
         println!("{}", result);
     }
 
@@ -0,0 +1,68 @@
+use std::io::Write;
+
+use clap::Parser;
+use cli::{
+    parse_config,
+    revelation_script::{ScriptFormat, script_steps, write_script},
+};
+use color_eyre::eyre::eyre;
+use data::{RunsFile, configdata::ConfigContest, contest_state::ContestState};
+use service::webcast::load_data_from_url_maybe;
+
+#[derive(Parser, Debug)]
+#[command(version, about, long_about = None)]
+/// Writes the script of a revelation, to rehearse the ceremony
+struct Args {
+    /// Webcast file
+    webcast: String,
+
+    /// Sede config file
+    #[clap(short = 's', long, default_value = "config/basic.toml")]
+    config: std::path::PathBuf,
+
+    /// Sede name, the contest title if absent
+    #[clap(long)]
+    sede: Option<String>,
+
+    /// Output format
+    #[clap(short = 'f', long, value_enum, default_value = "markdown")]
+    format: ScriptFormat,
+
+    /// Output file, the standard output if absent
+    #[clap(short = 'o', long)]
+    output: Option<std::path::PathBuf>,
+}
+
+#[tokio::main]
+async fn main() -> color_eyre::eyre::Result<()> {
+    let args = Args::parse();
+
+    let config = parse_config::<ConfigContest>(&args.config)?;
+    let contest = config.into_contest();
+    let sede = match &args.sede {
+        Some(name) => contest
+            .get_sede_nome_sede(name)
+            .ok_or_else(|| eyre!("sede not found: {name}"))?,
+        None => &contest.titulo,
+    };
+
+    let ContestState {
+        runs,
+        contest: contest_file,
+        ..
+    } = load_data_from_url_maybe(&args.webcast).await?;
+    let steps = script_steps(
+        contest.contest_file(contest_file, sede),
+        RunsFile::new(runs),
+        sede,
+    );
+
+    let mut out: Box<dyn Write> = match &args.output {
+        Some(path) => Box::new(std::io::BufWriter::new(std::fs::File::create(path)?)),
+        None => Box::new(std::io::stdout().lock()),
+    };
+    write_script(steps, args.format, &mut out)?;
+    out.flush()?;
+
+    Ok(())
+}
@@ -1,8 +1,15 @@
+use std::{sync::Arc, time::Duration};
+
 use clap::Parser;
-use cli::SimpleArgs;
+use cli::{SimpleArgs, SourceArgs};
 
 use service::{
-    app_config::AppConfig, http::HttpConfig, pair_arg::FromPairArg, sentry, volume::Volume,
+    app_config::{AppConfig, ConfigLoader},
+    http::{ConnectionLimits, HttpConfig},
+    pair_arg::FromPairArg,
+    sentry,
+    snapshot::SnapshotStore,
+    volume::Volume,
 };
 use tracing_subscriber::{EnvFilter, util::SubscriberInitExt};
 
@@ -17,13 +24,8 @@ struct SimpleParser {
     /// The TCP port to host the server
     port: u16,
 
-    #[clap(short = 'k')]
-    /// API Key for admin endpoints
-    server_api_key: Option<String>,
-
-    /// The webcast url from BOCA.
-    #[clap(short = 'i')]
-    url: Option<String>,
+    #[clap(flatten)]
+    source: SourceArgs,
 
     #[clap(short = 'v', long)]
     /// Maps a local FOLDER to a remote PATH.
@@ -31,6 +33,31 @@ struct SimpleParser {
     ///
     /// Expected format: FOLDER:PATH
     volume: Vec<FromPairArg<Volume>>,
+
+    #[clap(long)]
+    /// Directory where the contest state is saved,
+    /// and restored from when the server starts.
+    snapshot_dir: Option<String>,
+
+    #[clap(long, default_value_t = ConnectionLimits::default().max_connections)]
+    /// Websockets and event streams open at the same time.
+    max_connections: usize,
+
+    #[clap(long, default_value_t = ConnectionLimits::default().max_connections_per_ip)]
+    /// Websockets and event streams open at the same time, from a single IP.
+    max_connections_per_ip: usize,
+
+    #[clap(long, default_value_t = ConnectionLimits::default().idle_timeout.as_secs())]
+    /// Seconds until a websocket that does not answer pings is closed.
+    idle_timeout_secs: u64,
+
+    #[clap(long)]
+    /// Takes the client IP from `X-Forwarded-For`, when the server is behind a proxy.
+    trust_forwarded_for: bool,
+
+    #[clap(long, default_value_t = 60)]
+    /// Seconds without updates from the source until the contest is shown as stale.
+    stale_after_secs: u64,
 }
 
 #[tokio::main]
@@ -43,25 +70,45 @@ async fn main() -> color_eyre::eyre::Result<()> {
     let SimpleParser {
         args,
         port,
-        url,
+        source,
         volume: volumes,
-        server_api_key,
+        snapshot_dir,
+        max_connections,
+        max_connections_per_ip,
+        idle_timeout_secs,
+        trust_forwarded_for,
+        stale_after_secs,
     } = SimpleParser::parse();
 
     let complete = args.into_contest_and_secret()?;
 
-    let server_config = HttpConfig { port };
+    let server_config = HttpConfig {
+        port,
+        connection_limits: ConnectionLimits {
+            max_connections,
+            max_connections_per_ip,
+            idle_timeout: Duration::from_secs(idle_timeout_secs),
+            trust_forwarded_for,
+        },
+    };
 
     tracing::info!("\nSetting up sentry guard");
     let _guard = sentry::setup();
     server_v2::metrics::setup();
 
+    let config_loader: ConfigLoader = Arc::new(move || {
+        args.into_contest_and_secret()
+            .map_err(|err| service::errors::Error::InvalidConfig(format!("{err:?}")))
+    });
+
     let app_config = AppConfig {
         config: complete,
-        boca_url: url,
+        config_loader: Some(config_loader),
+        source: source.source(),
         server_config,
         volumes: volumes.into_iter().map(|x| x.into_inner()).collect(),
-        server_api_key,
+        snapshot_store: snapshot_dir.map(SnapshotStore::new),
+        stale_after: Duration::from_secs(stale_after_secs),
     };
 
     tracing::info!("\nMaratona Rustreimator rodando!");
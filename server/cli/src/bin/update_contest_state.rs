@@ -1,7 +1,13 @@
 use clap::Parser;
 
-use color_eyre::eyre;
-use service::{sentry, webcast};
+use cli::{
+    SourceArgs,
+    delta_upload::{DeltaUploader, Update},
+};
+use color_eyre::eyre::{self, OptionExt};
+use data::contest_state::UpdateAck;
+use reqwest::StatusCode;
+use service::{sentry, source::Source};
 use tracing::{debug, error};
 use tracing_subscriber::{EnvFilter, util::SubscriberInitExt};
 
@@ -9,17 +15,20 @@ use tracing_subscriber::{EnvFilter, util::SubscriberInitExt};
 #[command(version, about, long_about = None)]
 /// Maratona Rustrimeitor Server
 struct SimpleParser {
-    #[clap(short = 'k')]
-    /// API Key for admin endpoints
-    server_api_key: String,
+    #[clap(short = 'k', long)]
+    /// Token of the uploader role, from `regenerate_secrets`.
+    token: String,
 
-    /// The webcast url from BOCA.
-    #[clap(short = 'i')]
-    boca_url: String,
+    #[clap(flatten)]
+    source: SourceArgs,
 
     /// The webcast url from BOCA.
     #[clap(short = 's')]
     server_url: String,
+
+    #[clap(short = 'c', long)]
+    /// The contest key on the server, when it hosts more than one contest.
+    contest: Option<String>,
 }
 
 #[tokio::main]
@@ -30,47 +39,88 @@ async fn main() -> color_eyre::eyre::Result<()> {
         .init();
 
     let SimpleParser {
-        server_api_key,
-        boca_url,
+        token,
+        source,
         server_url,
+        contest,
     } = SimpleParser::parse();
 
+    let source = source
+        .source()
+        .ok_or_eyre("either a BOCA url, an event feed or a replay is required")?;
+
     tracing::info!("\nSetting up sentry guard");
     let _guard = sentry::setup();
 
-    db_update_loop(&server_api_key, &boca_url, &server_url).await?;
+    db_update_loop(&token, &source, &server_url, contest.as_deref()).await?;
 
     Ok(())
 }
 
+fn contests_url(server_url: &str, contest: Option<&str>, sequence: Option<u64>) -> String {
+    let query = contest
+        .map(|contest| format!("contest={contest}"))
+        .into_iter()
+        .chain(sequence.map(|sequence| format!("sequence={sequence}")))
+        .collect::<Vec<_>>();
+
+    if query.is_empty() {
+        format!("{server_url}/contests")
+    } else {
+        format!("{server_url}/contests?{}", query.join("&"))
+    }
+}
+
 #[allow(clippy::type_complexity)]
 pub async fn db_update_loop(
-    server_api_key: &str,
-    boca_url: &str,
+    token: &str,
+    source: &Source,
     server_url: &str,
+    contest: Option<&str>,
 ) -> eyre::Result<()> {
-    let dur = tokio::time::Duration::new(1, 0);
-    let mut interval = tokio::time::interval(dur);
-
     let client = reqwest::Client::new();
+    let mut uploader = DeltaUploader::new();
 
     loop {
-        interval.tick().await;
-
-        let contest_state = webcast::load_data_from_url_maybe(&boca_url).await?;
+        let contest_state = match source.load().await {
+            Ok(contest_state) => contest_state,
+            Err(err) => {
+                error!(%err, "failed loading the source");
+                tokio::time::sleep(source.next_delay()).await;
+                continue;
+            }
+        };
 
-        let result = client
-            .put(format!("{server_url}/contests"))
-            .json(&contest_state)
-            .header("apikey", server_api_key)
-            .send()
-            .await?;
+        let request = match uploader.next_update(contest_state) {
+            Update::Full { sequence, state } => client
+                .put(contests_url(server_url, contest, Some(sequence)))
+                .json(&state),
+            Update::Delta(delta) => client
+                .patch(contests_url(server_url, contest, None))
+                .json(&delta),
+        };
 
-        match result.error_for_status() {
-            Ok(_) => {
-                debug!("ok");
+        let result = match request.bearer_auth(token).send().await {
+            Ok(result) => result,
+            Err(err) => {
+                error!(%err, "failed sending to the server");
+                uploader.acknowledge(None);
+                tokio::time::sleep(source.next_delay()).await;
+                continue;
             }
-            Err(err) => error!(?err, "status error"),
-        }
+        };
+
+        // A conflict carries the last sequence the server applied.
+        let status = result.status();
+        let ack = if status.is_success() || status == StatusCode::CONFLICT {
+            debug!(?status, "ok");
+            result.json::<UpdateAck>().await.ok()
+        } else {
+            error!(?status, "status error");
+            None
+        };
+        uploader.acknowledge(ack);
+
+        tokio::time::sleep(source.next_delay()).await;
     }
 }
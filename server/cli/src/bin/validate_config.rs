@@ -0,0 +1,76 @@
+use clap::Parser;
+use cli::{
+    NamedSede,
+    validate_config::{SourceFile, validate},
+};
+use color_eyre::eyre::{self, OptionExt};
+use service::{pair_arg::FromPairArg, read_contest, webcast::read_webcast_contest};
+
+#[derive(Parser)]
+#[command(version, about, long_about = None)]
+/// Checks the sedes and secrets against the teams of the contest
+struct Args {
+    #[clap(short = 's', long, default_value = "config/basic.toml:default")]
+    /// The sedes config files, as in `simples`. Can be used multiple times.
+    sedes: Vec<FromPairArg<NamedSede>>,
+
+    #[clap(short = 'x', long)]
+    /// Secrets file. Can be used multiple times.
+    secret: Vec<String>,
+
+    /// The webcast url or zip from BOCA.
+    #[clap(short = 'i', conflicts_with = "contest_file")]
+    url: Option<String>,
+
+    #[clap(long)]
+    /// BOCA's contest file, instead of the webcast.
+    contest_file: Option<String>,
+}
+
+#[tokio::main]
+async fn main() -> eyre::Result<()> {
+    let Args {
+        sedes,
+        secret,
+        url,
+        contest_file,
+    } = Args::parse();
+
+    let contest = match (url, contest_file) {
+        // The url is left out, BOCA's webcast url carries its key.
+        (Some(url), _) if url.contains("://") => SourceFile {
+            path: "webcast contest".to_string(),
+            text: read_webcast_contest(&url).await?,
+        },
+        (Some(url), _) => SourceFile {
+            path: format!("{url}/contest"),
+            text: read_webcast_contest(&url).await?,
+        },
+        (None, Some(path)) => SourceFile::read(&path)?,
+        (None, None) => None.ok_or_eyre("either a webcast or a contest file is required")?,
+    };
+
+    let configs = sedes
+        .into_iter()
+        .map(|sede| SourceFile::read(&sede.into_inner().file))
+        .collect::<eyre::Result<Vec<_>>>()?;
+    let secrets = secret
+        .iter()
+        .map(|path| SourceFile::read(path))
+        .collect::<eyre::Result<Vec<_>>>()?;
+
+    let findings = validate(&configs, &secrets, &contest)?;
+    for finding in &findings {
+        println!("{finding}");
+    }
+
+    if !findings.is_empty() {
+        eyre::bail!("found {} problems in the config", findings.len());
+    }
+
+    println!(
+        "{} teams checked, no problems found",
+        read_contest(&contest.text)?.teams.len()
+    );
+    Ok(())
+}
@@ -0,0 +1,130 @@
+use data::{
+    RunsFile,
+    contest_state::{ContestDelta, ContestState, UpdateAck},
+};
+
+/// A full update is sent from time to time, so runs deleted in BOCA,
+/// and changes to the teams, reach the server.
+const FULL_UPDATE_EVERY: usize = 60;
+
+#[derive(Debug)]
+pub enum Update {
+    Full { sequence: u64, state: ContestState },
+    Delta(ContestDelta),
+}
+
+#[derive(Debug)]
+struct Sent {
+    sequence: u64,
+    runs: RunsFile,
+    logins: Vec<String>,
+}
+
+#[derive(Debug, Default)]
+/// Decides between full and delta updates, from the acknowledgements of the server.
+pub struct DeltaUploader {
+    next_sequence: u64,
+    deltas: usize,
+    acknowledged: Option<Sent>,
+    pending: Option<Sent>,
+}
+
+impl DeltaUploader {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn next_update(&mut self, state: ContestState) -> Update {
+        let sequence = self.next_sequence;
+        self.next_sequence += 1;
+
+        let runs = RunsFile::new(state.runs.clone());
+        let logins = state.contest.teams.keys().cloned().collect::<Vec<_>>();
+
+        let update = match &self.acknowledged {
+            Some(acknowledged)
+                if acknowledged.logins == logins && self.deltas < FULL_UPDATE_EVERY =>
+            {
+                self.deltas += 1;
+                let mut previous = acknowledged.runs.clone();
+                Update::Delta(ContestDelta {
+                    base: acknowledged.sequence,
+                    sequence,
+                    time: state.time,
+                    runs: previous.refresh(runs.sorted()),
+                })
+            }
+            _ => {
+                self.deltas = 0;
+                Update::Full { sequence, state }
+            }
+        };
+
+        self.pending = Some(Sent {
+            sequence,
+            runs,
+            logins,
+        });
+        update
+    }
+
+    /// Records the answer of the server to the last update.
+    /// Anything but the sequence just sent makes the next update a full one.
+    pub fn acknowledge(&mut self, ack: Option<UpdateAck>) {
+        self.acknowledged = match self.pending.take() {
+            Some(sent) if ack.is_some_and(|ack| ack.sequence == Some(sent.sequence)) => Some(sent),
+            _ => None,
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use data::{Answer, ContestFile, Team, test_util::run};
+
+    use super::*;
+
+    fn state(answers: &[Answer]) -> ContestState {
+        let team = Team::new("team1", "escola", "Time 1".to_string());
+        ContestState {
+            runs: answers
+                .iter()
+                .enumerate()
+                .map(|(i, answer)| run(i as i64, "team1", 10, answer.clone()))
+                .collect(),
+            time: 20,
+            contest: ContestFile::new("c".to_string(), vec![team], 20, 300, 240, 20, 1),
+        }
+    }
+
+    #[test]
+    fn test_delta_after_acknowledged_full_update() {
+        let mut uploader = DeltaUploader::new();
+
+        let Update::Full { sequence, .. } =
+            uploader.next_update(state(&[Answer::Wait { run_id: 0 }]))
+        else {
+            panic!("first update should be full");
+        };
+        uploader.acknowledge(Some(UpdateAck {
+            sequence: Some(sequence),
+        }));
+
+        let update = uploader.next_update(state(&[
+            Answer::No { run_id: 0 },
+            Answer::Wait { run_id: 1 },
+        ]));
+        let Update::Delta(delta) = update else {
+            panic!("second update should be a delta");
+        };
+        assert_eq!(delta.base, sequence);
+        assert_eq!(delta.runs.len(), 2);
+
+        // The server lost track of the sequence, e.g. after a restart.
+        uploader.acknowledge(Some(UpdateAck { sequence: None }));
+        assert!(matches!(
+            uploader.next_update(state(&[])),
+            Update::Full { .. }
+        ));
+    }
+}
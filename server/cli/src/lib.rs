@@ -1,14 +1,20 @@
 use std::collections::HashMap;
+use std::io::BufRead;
 
 use color_eyre::Section;
-use data::configdata::{ConfigContest, ConfigSecret, Contest, Secret};
+use data::configdata::{ConfigContest, ConfigSecret, Contest, Metadata, PollPolicy, Secret};
 use serde::Deserialize;
 use service::pair_arg::{FromPairArg, PairArg};
+use service::replay::{Replay, ReplayControl};
+use service::source::Source;
 
+pub mod delta_upload;
+pub mod revelation_script;
 pub mod test_revelation;
+pub mod validate_config;
 
 #[tracing::instrument(err)]
-fn parse_config<T>(path: &std::path::Path) -> color_eyre::eyre::Result<T>
+pub fn parse_config<T>(path: &std::path::Path) -> color_eyre::eyre::Result<T>
 where
     T: for<'a> Deserialize<'a>,
 {
@@ -21,8 +27,8 @@ where
 
 #[derive(Debug, Clone)]
 pub struct NamedSede {
-    file: String,
-    name: String,
+    pub file: String,
+    pub name: String,
 }
 
 impl From<PairArg> for NamedSede {
@@ -50,17 +56,121 @@ pub struct SimpleArgs {
     pub secret: Vec<String>,
 }
 
+#[derive(clap::Args, Debug)]
+pub struct SourceArgs {
+    /// BOCA's webcast: an url, a zip file, a directory with the extracted files,
+    /// or `-` for contest states piped to stdin as JSON lines.
+    #[clap(short = 'i', conflicts_with = "event_feed")]
+    pub url: Option<String>,
+
+    /// The CLICS Contest API event feed url, used instead of BOCA's webcast.
+    #[clap(long)]
+    pub event_feed: Option<String>,
+
+    /// Milliseconds between reads of the source.
+    #[clap(long)]
+    pub poll_interval_ms: Option<u64>,
+
+    /// Longest wait between reads of the source, after failed reads, in milliseconds.
+    #[clap(long)]
+    pub max_backoff_ms: Option<u64>,
+
+    /// Replays a recorded webcast as if it were live, read as with `-i`, but not from stdin.
+    /// Commands from stdin drive the clock:
+    /// pause, resume, seek MINUTE, freeze, speed X and status.
+    #[clap(long, conflicts_with_all = ["url", "event_feed"], value_parser = recorded_webcast)]
+    pub replay: Option<String>,
+
+    /// Speed of the replay, 60 plays a contest minute each second.
+    #[clap(long, default_value_t = 1.0, value_parser = positive_speed)]
+    pub replay_speed: f64,
+
+    /// Contest minute the replay starts from, negative for the countdown.
+    #[clap(long, default_value_t = 0, allow_negative_numbers = true)]
+    pub replay_from: i64,
+}
+
+/// Stdin is read for the replay commands, so the recording cannot come from it.
+fn recorded_webcast(s: &str) -> Result<String, String> {
+    match s {
+        "-" => Err("the replay reads its commands from stdin, expected a recorded webcast".into()),
+        _ => Ok(s.to_string()),
+    }
+}
+
+fn positive_speed(s: &str) -> Result<f64, String> {
+    match s.parse() {
+        Ok(speed) if speed > 0.0 => Ok(speed),
+        _ => Err(format!("expected a positive speed, got {s}")),
+    }
+}
+
+/// Drives the replay with the commands read from stdin.
+fn control_replay_from_stdin(control: ReplayControl) {
+    std::thread::spawn(move || {
+        for line in std::io::stdin().lock().lines().map_while(Result::ok) {
+            if line.trim().is_empty() {
+                continue;
+            }
+            match line.parse().and_then(|command| control.apply(command)) {
+                Ok(status) => tracing::info!(%status, "replay"),
+                Err(err) => tracing::warn!(%err, "replay command failed"),
+            }
+        }
+    });
+}
+
+impl SourceArgs {
+    pub fn source(&self) -> Option<Source> {
+        let source = match (&self.url, &self.event_feed, &self.replay) {
+            (Some(url), _, _) => Source::webcast(url),
+            (None, Some(event_feed), _) => Source::event_feed(event_feed),
+            (None, None, Some(replay)) => {
+                let control = ReplayControl::new(self.replay_from * 60, self.replay_speed);
+                control_replay_from_stdin(control.clone());
+                Source::new(Box::new(Replay::new(replay, control)))
+            }
+            (None, None, None) => return None,
+        };
+
+        let default = PollPolicy::default();
+        Some(source.with_policy(PollPolicy {
+            interval_ms: self.poll_interval_ms.unwrap_or(default.interval_ms),
+            max_backoff_ms: self.max_backoff_ms.unwrap_or(default.max_backoff_ms),
+        }))
+    }
+}
+
 fn gather_secrets(secrets: &[String]) -> color_eyre::Result<ConfigSecret> {
-    let mut empty = ConfigSecret { secrets: vec![] };
+    let mut empty = ConfigSecret::default();
     for path in secrets {
         let secret = parse_config::<ConfigSecret>(std::path::Path::new(path))
             .map_err(|e| e.with_note(|| "Should be able to parse secret file."))?;
 
         empty.secrets.extend(secret.secrets);
+        empty.tokens.extend(secret.tokens);
     }
     Ok(empty)
 }
 
+/// Reads the metadata file referenced by the config, if there is one.
+fn load_metadata(
+    config_path: &std::path::Path,
+    config: &mut ConfigContest,
+) -> color_eyre::Result<()> {
+    if let Some(metadata_file) = &config.metadata_file {
+        let path = config_path
+            .parent()
+            .unwrap_or(std::path::Path::new(""))
+            .join(metadata_file);
+        let metadata = parse_config::<Metadata>(&path)
+            .map_err(|e| e.with_note(|| "Should be able to parse the metadata file."))?;
+
+        config.metadata = Some(metadata);
+    }
+    Ok(())
+}
+
 impl SimpleArgs {
     #[tracing::instrument(err)]
     pub fn into_contest_and_secret(
@@ -78,8 +188,9 @@ impl SimpleArgs {
 
         for sede in sedes {
             let NamedSede { file, name } = sede.clone().into_inner();
-            let config = parse_config::<ConfigContest>(std::path::Path::new(&file))
+            let mut config = parse_config::<ConfigContest>(std::path::Path::new(&file))
                 .map_err(|e| e.with_note(|| "Should be able to parse the config."))?;
+            load_metadata(std::path::Path::new(&file), &mut config)?;
 
             let contest = config.clone().into_contest();
             let secret = main_config_secret.into_secret(salt.clone(), &contest);
@@ -90,3 +201,41 @@ impl SimpleArgs {
         Ok(result)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use clap::Parser;
+
+    use super::*;
+
+    #[test]
+    fn test_load_metadata() -> color_eyre::Result<()> {
+        let config_path = std::path::Path::new("../../config/basic.toml");
+        let mut config = parse_config::<ConfigContest>(config_path)?;
+        config.metadata_file = Some("basic_metadata.toml".to_string());
+
+        load_metadata(config_path, &mut config)?;
+
+        let metadata = config.metadata.expect("metadata should be loaded");
+        assert_eq!(
+            metadata
+                .team("teambrsc1")
+                .and_then(|t| t.short_school.as_deref()),
+            Some("UFSC")
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_replay_rejects_stdin() {
+        #[derive(Parser)]
+        struct Args {
+            #[clap(flatten)]
+            source: SourceArgs,
+        }
+
+        assert!(Args::try_parse_from(["cli", "--replay", "-"]).is_err());
+        let args = Args::try_parse_from(["cli", "--replay", "webcast.zip"]).unwrap();
+        assert_eq!(args.source.replay.as_deref(), Some("webcast.zip"));
+    }
+}
@@ -0,0 +1,225 @@
+use std::io::Write;
+
+use data::{
+    Answer, ContestFile, RunsFile,
+    configdata::{Color, Sede},
+    revelation::RevelationDriver,
+};
+use serde::Serialize;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum ScriptFormat {
+    Markdown,
+    Json,
+    Csv,
+}
+
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+/// One step of the revelation, as read by the ceremony host.
+pub struct ScriptStep {
+    pub step: u32,
+    pub team_login: String,
+    pub team_name: String,
+    pub problem: String,
+    pub verdict: &'static str,
+    pub rank_before: usize,
+    pub rank_after: usize,
+    pub medal_before: Option<Color>,
+    pub medal_after: Option<Color>,
+}
+
+fn verdict(answer: &Answer) -> &'static str {
+    match answer {
+        Answer::Yes { .. } => "accepted",
+        Answer::No { .. } => "rejected",
+        Answer::Wait { .. } => "pending",
+        Answer::Unk { .. } => "unknown",
+    }
+}
+
+fn medal_name(medal: Option<Color>) -> &'static str {
+    match medal {
+        Some(Color::Gold) => "ouro",
+        Some(Color::Silver) => "prata",
+        Some(Color::Bronze) => "bronze",
+        _ => "",
+    }
+}
+
+/// Reveals the whole contest of `sede`, one step at a time.
+/// Steps where a team leaves the queue without revealing a run are skipped.
+pub fn script_steps(
+    contest: ContestFile,
+    runs: RunsFile,
+    sede: &Sede,
+) -> impl Iterator<Item = ScriptStep> {
+    let mut driver = RevelationDriver::new(contest.filter_sede(sede), runs);
+
+    std::iter::from_fn(move || {
+        while !driver.is_empty() {
+            driver.reveal_step();
+            let reveal = driver.last_reveal()?;
+
+            if let Some((problem, answer)) = reveal.revealed {
+                let rank_before = reveal.placement_before;
+                let rank_after = reveal.team.placement_global;
+
+                return Some(ScriptStep {
+                    step: driver.step(),
+                    team_login: reveal.team.login.clone(),
+                    team_name: reveal.team.name.clone(),
+                    problem: problem.to_string(),
+                    verdict: verdict(answer),
+                    rank_before,
+                    rank_after,
+                    medal_before: sede.premio(rank_before),
+                    medal_after: sede.premio(rank_after),
+                });
+            }
+        }
+        None
+    })
+}
+
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+/// Writes the script as it is revealed, so long contests do not need to fit in memory.
+pub fn write_script(
+    steps: impl Iterator<Item = ScriptStep>,
+    format: ScriptFormat,
+    out: &mut impl Write,
+) -> color_eyre::eyre::Result<()> {
+    match format {
+        ScriptFormat::Markdown => {
+            writeln!(
+                out,
+                "| Passo | Time | Problema | Veredito | Posição | Medalha |"
+            )?;
+            writeln!(out, "|---|---|---|---|---|---|")?;
+        }
+        ScriptFormat::Json => write!(out, "[")?,
+        ScriptFormat::Csv => writeln!(
+            out,
+            "step,team_login,team_name,problem,verdict,rank_before,rank_after,medal_before,medal_after"
+        )?,
+    }
+
+    for (i, step) in steps.enumerate() {
+        match format {
+            ScriptFormat::Markdown => {
+                let medal = if step.medal_before != step.medal_after {
+                    format!(
+                        "{} → {}",
+                        medal_name(step.medal_before),
+                        medal_name(step.medal_after)
+                    )
+                } else {
+                    medal_name(step.medal_after).to_string()
+                };
+                writeln!(
+                    out,
+                    "| {} | {} ({}) | {} | {} | {} → {} | {} |",
+                    step.step,
+                    step.team_name.replace('|', "\\|"),
+                    step.team_login,
+                    step.problem,
+                    step.verdict,
+                    step.rank_before,
+                    step.rank_after,
+                    medal.trim(),
+                )?;
+            }
+            ScriptFormat::Json => {
+                if i > 0 {
+                    write!(out, ",")?;
+                }
+                write!(out, "\n  ")?;
+                serde_json::to_writer(&mut *out, &step)?;
+            }
+            ScriptFormat::Csv => writeln!(
+                out,
+                "{},{},{},{},{},{},{},{},{}",
+                step.step,
+                csv_field(&step.team_login),
+                csv_field(&step.team_name),
+                step.problem,
+                step.verdict,
+                step.rank_before,
+                step.rank_after,
+                medal_name(step.medal_before),
+                medal_name(step.medal_after),
+            )?,
+        }
+    }
+
+    if format == ScriptFormat::Json {
+        writeln!(out, "\n]")?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use data::{RunsFile, configdata::SedeEntry, contest_state::ContestState};
+    use service::webcast::load_data_from_url_maybe;
+
+    use super::*;
+
+    const WEBCAST: &str = "../../tests/inputs/webcast_jones.zip";
+
+    fn sede() -> Sede {
+        toml::from_str::<SedeEntry>("name = \"Geral\"\ncodes = [\"\"]")
+            .expect("valid sede")
+            .into_sede()
+    }
+
+    #[tokio::test]
+    async fn test_script_follows_golden_model() -> color_eyre::eyre::Result<()> {
+        let ContestState { runs, contest, .. } = load_data_from_url_maybe(WEBCAST).await?;
+        let golden = std::fs::read_to_string(format!("{WEBCAST}.revelation"))?;
+        let golden = golden.lines().collect::<Vec<_>>();
+
+        let steps = script_steps(contest, RunsFile::new(runs), &sede()).collect::<Vec<_>>();
+
+        assert!(!steps.is_empty());
+        for step in &steps {
+            let (login, _) = golden[step.step as usize - 1]
+                .split_once(", ")
+                .expect("golden model line");
+            assert_eq!(step.team_login, login);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_write_csv() -> color_eyre::eyre::Result<()> {
+        let step = ScriptStep {
+            step: 1,
+            team_login: "team1".to_string(),
+            team_name: "Os \"Brabos\", do C".to_string(),
+            problem: "A".to_string(),
+            verdict: "accepted",
+            rank_before: 4,
+            rank_after: 3,
+            medal_before: None,
+            medal_after: Some(Color::Bronze),
+        };
+
+        let mut out = Vec::new();
+        write_script(std::iter::once(step), ScriptFormat::Csv, &mut out)?;
+
+        assert_eq!(
+            String::from_utf8(out)?.lines().nth(1),
+            Some("1,team1,\"Os \"\"Brabos\"\", do C\",A,accepted,4,3,,bronze")
+        );
+        Ok(())
+    }
+}
@@ -1,14 +1,21 @@
 use data::{RunsFile, contest_state::ContestState, revelation::RevelationDriver};
 use service::webcast::load_data_from_url_maybe;
 
-pub async fn build_revelation(input_file: &str) -> color_eyre::eyre::Result<Vec<String>> {
+async fn load_driver(input_file: &str) -> color_eyre::eyre::Result<RevelationDriver> {
     let ContestState {
         runs: runs_data,
         time: _,
         contest: contest_data,
     } = load_data_from_url_maybe(input_file).await?;
 
-    let mut driver = RevelationDriver::new(contest_data, RunsFile::new(runs_data));
+    Ok(RevelationDriver::new(
+        contest_data,
+        RunsFile::new(runs_data),
+    ))
+}
+
+pub async fn build_revelation(input_file: &str) -> color_eyre::eyre::Result<Vec<String>> {
+    let mut driver = load_driver(input_file).await?;
     let mut result = Vec::new();
 
     while !driver.is_empty() {
@@ -18,6 +25,23 @@ pub async fn build_revelation(input_file: &str) -> color_eyre::eyre::Result<Vec<
     Ok(result)
 }
 
+/// Reveals everything, then steps back to the start,
+/// returning the steps in the order they were revealed.
+pub async fn build_revelation_backwards(input_file: &str) -> color_eyre::eyre::Result<Vec<String>> {
+    let mut driver = load_driver(input_file).await?;
+    while !driver.is_empty() {
+        driver.reveal_step();
+    }
+
+    let mut result = Vec::new();
+    while driver.step() > 0 {
+        driver.back_one();
+        result.push(format!("{}, {}", driver.peek().unwrap(), driver.len()));
+    }
+    result.reverse();
+    Ok(result)
+}
+
 #[cfg(test)]
 mod test {
     use std::fs::File;
@@ -61,6 +85,23 @@ mod test {
             .inspect_err(|_err| eprint!("test: {test_input}"))
     }
 
+    #[rstest]
+    #[case("../../tests/inputs/webcast_jones_2021_judge_submission.zip")]
+    #[case("../../tests/inputs/webcast_jones_08_2021.zip")]
+    #[case("../../tests/inputs/webcast_jones_2021.zip")]
+    #[case("../../tests/inputs/webcast_jones.zip")]
+    #[case("../../tests/inputs/1a_fase_2021_frozen_unlocked_argentina.zip")]
+    #[tokio::test]
+    async fn test_golden_model_backwards(#[case] test_input: &str) -> color_eyre::eyre::Result<()> {
+        let model =
+            read_lines(format!("{test_input}.revelation"))?.collect::<Result<Vec<_>, _>>()?;
+        let reveals = super::build_revelation_backwards(test_input).await?;
+
+        assert_eq!(model, reveals, "golden models differ {}", test_input);
+
+        Ok(())
+    }
+
     #[cfg(feature = "slow_tests")]
     #[rstest]
     #[case("../../tests/inputs/webcast.rinhadecalouros.zip")]
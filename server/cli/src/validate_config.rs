@@ -0,0 +1,379 @@
+use std::{collections::BTreeSet, fmt::Display};
+
+use data::{
+    ContestFile,
+    configdata::{ConfigContest, ConfigSecret, Sede, SedeEntry},
+};
+use service::read_contest;
+
+#[derive(Debug, Clone)]
+/// A config file, kept as text so problems can point to their lines.
+pub struct SourceFile {
+    pub path: String,
+    pub text: String,
+}
+
+impl SourceFile {
+    pub fn read(path: &str) -> color_eyre::eyre::Result<Self> {
+        Ok(Self {
+            path: path.to_string(),
+            text: std::fs::read_to_string(path)?,
+        })
+    }
+
+    /// Line, starting at 1, of the entry with this `name`.
+    fn line_of_name(&self, name: &str) -> Option<usize> {
+        self.text
+            .lines()
+            .position(|line| {
+                line.split_once('=').is_some_and(|(key, value)| {
+                    key.trim() == "name" && value.trim().trim_matches(['"', '\'']) == name
+                })
+            })
+            .map(|i| i + 1)
+    }
+
+    /// Line, starting at 1, of the team with this `login` in BOCA's contest file.
+    fn line_of_team(&self, login: &str) -> Option<usize> {
+        self.text
+            .lines()
+            .position(|line| {
+                line.split_once('\x1c')
+                    .is_some_and(|(team_login, _)| team_login == login)
+            })
+            .map(|i| i + 1)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Problem {
+    /// The team is in no sede of the `config`, so it only shows in the main scoreboard.
+    UnmatchedTeam { login: String, config: String },
+    /// Some teams are in both sedes, but neither sede contains the other.
+    OverlappingSedes {
+        first: String,
+        second: String,
+        teams: Vec<String>,
+    },
+    /// `ConfigSecret::into_secret` drops secrets of sedes that do not exist.
+    UnknownSecretSede { name: String },
+    /// Medals must be ordered: `ouro <= prata <= bronze`, where 0 means no medal.
+    UnorderedMedals {
+        sede: String,
+        ouro: usize,
+        prata: usize,
+        bronze: usize,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Finding {
+    pub path: String,
+    pub line: Option<usize>,
+    pub problem: Problem,
+}
+
+impl Display for Finding {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.line {
+            Some(line) => write!(f, "{}:{line}: ", self.path)?,
+            None => write!(f, "{}: ", self.path)?,
+        }
+
+        match &self.problem {
+            Problem::UnmatchedTeam { login, config } => {
+                write!(f, "team {login} is in no sede of {config}")
+            }
+            Problem::OverlappingSedes {
+                first,
+                second,
+                teams,
+            } => write!(
+                f,
+                "sedes {first} and {second} share teams without one containing the other: {}",
+                teams.join(", ")
+            ),
+            Problem::UnknownSecretSede { name } => {
+                write!(f, "secret of sede {name}, but there is no such sede")
+            }
+            Problem::UnorderedMedals {
+                sede,
+                ouro,
+                prata,
+                bronze,
+            } => write!(
+                f,
+                "medals of sede {sede} are not ordered: ouro = {ouro}, prata = {prata}, bronze = {bronze}"
+            ),
+        }
+    }
+}
+
+fn teams_of(sede: &Sede, contest: &ContestFile) -> BTreeSet<String> {
+    contest
+        .teams
+        .values()
+        .filter(|team| sede.team_belongs(team))
+        .map(|team| team.login.clone())
+        .collect()
+}
+
+/// Checks the configs and secrets against the teams of BOCA's contest file.
+pub fn validate(
+    configs: &[SourceFile],
+    secrets: &[SourceFile],
+    contest_file: &SourceFile,
+) -> color_eyre::eyre::Result<Vec<Finding>> {
+    let contest = read_contest(&contest_file.text)?;
+    let mut findings = vec![];
+    for config in configs {
+        findings.extend(validate_config(config, contest_file, &contest)?);
+    }
+    findings.extend(validate_secrets(configs, secrets)?);
+    Ok(findings)
+}
+
+fn validate_config(
+    config: &SourceFile,
+    contest_file: &SourceFile,
+    contest: &ContestFile,
+) -> color_eyre::eyre::Result<Vec<Finding>> {
+    let config_contest: ConfigContest = toml::from_str(&config.text)?;
+    let mut findings = vec![];
+
+    let finding = |line: Option<usize>, problem| Finding {
+        path: config.path.clone(),
+        line,
+        problem,
+    };
+
+    let entries = config_contest.sedes.clone().unwrap_or_default();
+
+    for SedeEntry {
+        name,
+        ouro,
+        prata,
+        bronze,
+        ..
+    } in std::iter::once(&config_contest.titulo).chain(&entries)
+    {
+        let given = [ouro, prata, bronze]
+            .into_iter()
+            .filter(|&&medal| medal != 0)
+            .collect::<Vec<_>>();
+        if !given.is_sorted() {
+            findings.push(finding(
+                config.line_of_name(name),
+                Problem::UnorderedMedals {
+                    sede: name.clone(),
+                    ouro: *ouro,
+                    prata: *prata,
+                    bronze: *bronze,
+                },
+            ));
+        }
+    }
+
+    let sedes = entries
+        .iter()
+        .map(|entry| (entry, teams_of(&entry.into_sede(), contest)))
+        .collect::<Vec<_>>();
+
+    if !sedes.is_empty() {
+        for login in contest.teams.keys() {
+            if !sedes.iter().any(|(_, teams)| teams.contains(login)) {
+                findings.push(Finding {
+                    path: contest_file.path.clone(),
+                    line: contest_file.line_of_team(login),
+                    problem: Problem::UnmatchedTeam {
+                        login: login.clone(),
+                        config: config.path.clone(),
+                    },
+                });
+            }
+        }
+    }
+
+    for (i, (first, first_teams)) in sedes.iter().enumerate() {
+        for (second, second_teams) in &sedes[i + 1..] {
+            let shared = first_teams
+                .intersection(second_teams)
+                .cloned()
+                .collect::<Vec<_>>();
+
+            let nested = first_teams.is_subset(second_teams) || second_teams.is_subset(first_teams);
+            if !shared.is_empty() && !nested {
+                findings.push(finding(
+                    config.line_of_name(&second.name),
+                    Problem::OverlappingSedes {
+                        first: first.name.clone(),
+                        second: second.name.clone(),
+                        teams: shared,
+                    },
+                ));
+            }
+        }
+    }
+
+    Ok(findings)
+}
+
+/// The secrets are shared by every config, so a secret is only unknown when no config has its sede.
+fn validate_secrets(
+    configs: &[SourceFile],
+    secrets: &[SourceFile],
+) -> color_eyre::eyre::Result<Vec<Finding>> {
+    let contests = configs
+        .iter()
+        .map(|config| Ok(toml::from_str::<ConfigContest>(&config.text)?.into_contest()))
+        .collect::<color_eyre::eyre::Result<Vec<_>>>()?;
+    let mut findings = vec![];
+
+    for secret_file in secrets {
+        let config_secret: ConfigSecret = toml::from_str(&secret_file.text)?;
+        for secret in &config_secret.secrets {
+            if contests
+                .iter()
+                .all(|contest| contest.get_sede_nome_sede(&secret.name).is_none())
+            {
+                findings.push(Finding {
+                    path: secret_file.path.clone(),
+                    line: secret_file.line_of_name(&secret.name),
+                    problem: Problem::UnknownSecretSede {
+                        name: secret.name.clone(),
+                    },
+                });
+            }
+        }
+    }
+
+    Ok(findings)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn source(path: &str, text: &str) -> SourceFile {
+        SourceFile {
+            path: path.to_string(),
+            text: text.to_string(),
+        }
+    }
+
+    /// BOCA's contest file, with a team for each login.
+    fn contest(logins: &[&str]) -> SourceFile {
+        let mut text = format!("c\n300\x1c0\x1c240\x1c20\n{}\x1c2\n", logins.len());
+        for login in logins {
+            text.push_str(&format!("{login}\x1cescola\x1c{login}\n"));
+        }
+        source("contest", &text)
+    }
+
+    #[test]
+    fn test_validate() -> color_eyre::eyre::Result<()> {
+        let config = source(
+            "sedes.toml",
+            r#"
+[titulo]
+name = "Brasil"
+codes = [""]
+ouro = 3
+prata = 2
+
+[[sedes]]
+name = "Sul"
+codes = ["^teambrs"]
+ouro = 1
+prata = 0
+bronze = 3
+
+[[sedes]]
+name = "Santa Catarina"
+codes = ["^teambrsc"]
+
+[[sedes]]
+name = "Florianopolis"
+codes = ["^teambrsc1", "^teambrsp"]
+"#,
+        );
+        let secret = source(
+            "secrets.toml",
+            r#"
+[[secrets]]
+name = "Sul"
+secret = "a"
+
+[[secrets]]
+name = "Norte"
+secret = "b"
+"#,
+        );
+        let contest = contest(&[
+            "teambrsc1",
+            "teambrsc2",
+            "teambrsp1",
+            "teambrpr1",
+            "teambrce1",
+        ]);
+
+        let findings = validate(&[config], &[secret], &contest)?;
+
+        assert_eq!(
+            findings.iter().map(|f| f.to_string()).collect::<Vec<_>>(),
+            vec![
+                "sedes.toml:3: medals of sede Brasil are not ordered: ouro = 3, prata = 2, bronze = 3",
+                "contest:8: team teambrce1 is in no sede of sedes.toml",
+                "contest:7: team teambrpr1 is in no sede of sedes.toml",
+                "sedes.toml:20: sedes Santa Catarina and Florianopolis share teams without one containing the other: teambrsc1",
+                "secrets.toml:7: secret of sede Norte, but there is no such sede",
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_secrets_of_any_config() -> color_eyre::eyre::Result<()> {
+        let brasil = source(
+            "brasil.toml",
+            r#"
+[titulo]
+name = "Brasil"
+codes = ["^teambr"]
+"#,
+        );
+        let bolivia = source(
+            "bolivia.toml",
+            r#"
+[titulo]
+name = "Bolivia"
+codes = ["^teambo"]
+"#,
+        );
+        let secret = source(
+            "secrets.toml",
+            r#"
+[[secrets]]
+name = "Brasil"
+secret = "a"
+
+[[secrets]]
+name = "Bolivia"
+secret = "b"
+
+[[secrets]]
+name = "Chile"
+secret = "c"
+"#,
+        );
+        let contest = contest(&[]);
+
+        let findings = validate(&[brasil, bolivia], &[secret], &contest)?;
+
+        assert_eq!(
+            findings.iter().map(|f| f.to_string()).collect::<Vec<_>>(),
+            vec!["secrets.toml:11: secret of sede Chile, but there is no such sede"]
+        );
+        Ok(())
+    }
+}
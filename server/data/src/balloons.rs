@@ -0,0 +1,106 @@
+use std::collections::HashSet;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    Answer, ContestFile, Letter, RunsFile, TimeFile, annotate_first_solved::annotate_first_solved,
+    configdata::Sede,
+};
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+/// A balloon to be delivered to a team, for the first accepted run of a problem.
+pub struct Balloon {
+    pub run_id: i64,
+    pub team_login: String,
+    pub team_name: String,
+    pub escola: String,
+    pub problem: Letter,
+    pub time: TimeFile,
+    /// First team to solve the problem in the contest.
+    pub is_first: bool,
+    pub delivered: bool,
+}
+
+/// Balloons of the teams of `sede`, in the order the problems were solved.
+///
+/// First solves are computed over the whole contest, as in the scoreboard.
+pub fn balloons(
+    contest: &ContestFile,
+    runs: &RunsFile,
+    sede: &Sede,
+    delivered: &HashSet<i64>,
+) -> Vec<Balloon> {
+    let mut runs = runs.sorted();
+    annotate_first_solved(&mut HashSet::new(), runs.iter_mut());
+
+    let mut solved = HashSet::new();
+    runs.into_iter()
+        .filter_map(|run| {
+            let Answer::Yes { is_first, .. } = run.answer else {
+                return None;
+            };
+            if !sede.team_belongs_str(&run.team_login)
+                || !solved.insert((run.team_login.clone(), run.prob.clone()))
+            {
+                return None;
+            }
+            let team = contest.teams.get(&run.team_login)?;
+
+            Some(Balloon {
+                run_id: run.id,
+                team_login: run.team_login,
+                team_name: team.name.clone(),
+                escola: team.escola.clone(),
+                problem: run.prob,
+                time: run.time,
+                is_first,
+                delivered: delivered.contains(&run.id),
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        RunTuple, Team,
+        configdata::SedeEntry,
+        test_util::{run, yes},
+    };
+
+    #[test]
+    fn test_one_balloon_per_problem() {
+        let teams = ["teambr1", "teambr2", "teammx1"]
+            .into_iter()
+            .map(|login| Team::new(login, "escola", login.to_string()))
+            .collect();
+        let contest = ContestFile::new("c".to_string(), teams, 0, 300, 240, 20, 2);
+        let runs = RunsFile::new(vec![
+            run(1, "teammx1", 10, yes(1, 10)),
+            run(2, "teambr1", 20, Answer::No { run_id: 2 }),
+            run(3, "teambr1", 30, yes(3, 30)),
+            run(4, "teambr1", 40, yes(4, 40)),
+            RunTuple {
+                prob: "B".parse().unwrap(),
+                ..run(5, "teambr2", 50, yes(5, 50))
+            },
+        ]);
+        let sede = SedeEntry {
+            name: "br".to_string(),
+            codes: serde_json::from_value(serde_json::json!(["teambr"])).unwrap(),
+            ..SedeEntry::default()
+        }
+        .into_sede();
+
+        let balloons = balloons(&contest, &runs, &sede, &HashSet::from([5]));
+
+        assert_eq!(
+            balloons
+                .iter()
+                .map(|b| (b.run_id, b.is_first, b.delivered))
+                .collect::<Vec<_>>(),
+            vec![(3, false, false), (5, true, true)]
+        );
+    }
+}
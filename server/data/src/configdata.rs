@@ -8,7 +8,7 @@ use regex::RegexSet;
 use serde::{Deserialize, Serialize};
 use utoipa::ToSchema;
 
-use crate::Team;
+use crate::{ContestFile, Letter, Team, scoring::ScoringPolicy};
 
 #[derive(Debug, Clone, Default, Derivative, ToSchema)]
 #[derivative(PartialEq, Eq)]
@@ -85,7 +85,7 @@ impl PartialEq for Sede {
     }
 }
 
-#[derive(Deserialize, Serialize, Debug, Clone, Copy)]
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Color {
     Red,
     Gold,
@@ -133,6 +133,111 @@ pub struct ConfigContest {
     pub titulo: SedeEntry,
     /// A site entry.
     pub sedes: Option<Vec<SedeEntry>>,
+    /// Scoring policy, overrides the default ICPC rules.
+    #[serde(default)]
+    pub scoring: Option<ScoringPolicy>,
+    /// Path to a metadata file, relative to this config file.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub metadata_file: Option<String>,
+    /// Team and problem metadata, read from `metadata_file`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub metadata: Option<Metadata>,
+    /// BOCA's webcast url of this contest, when it is not the server's main source.
+    /// Never sent to the clients, the url carries BOCA's webcast key.
+    #[serde(default, skip_serializing)]
+    pub boca_url: Option<String>,
+    /// CLICS event feed url of this contest, used instead of `boca_url`.
+    #[serde(default, skip_serializing)]
+    pub event_feed: Option<String>,
+    /// How often the source of this contest is read.
+    #[serde(default, skip_serializing)]
+    pub poll: Option<PollPolicy>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, ToSchema)]
+#[serde(default)]
+/// How often a contest source is read.
+pub struct PollPolicy {
+    /// Milliseconds between reads.
+    pub interval_ms: u64,
+    /// Longest wait after failed reads, in milliseconds.
+    /// The wait doubles on each failure, starting from `interval_ms`.
+    pub max_backoff_ms: u64,
+}
+
+impl Default for PollPolicy {
+    fn default() -> Self {
+        Self {
+            interval_ms: 1000,
+            max_backoff_ms: 30_000,
+        }
+    }
+}
+
+impl PollPolicy {
+    /// The wait before the next read, after `failures` failed reads in a row.
+    pub fn delay(&self, failures: u32) -> std::time::Duration {
+        let backoff = self
+            .interval_ms
+            .saturating_mul(1 << failures.min(16))
+            .min(self.max_backoff_ms.max(self.interval_ms));
+        std::time::Duration::from_millis(backoff)
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq, ToSchema)]
+/// Extra information about a team, not available in BOCA.
+pub struct TeamMetadata {
+    /// ISO 3166-1 alpha-2 country code.
+    pub country: Option<String>,
+    /// Short name of the school.
+    pub short_school: Option<String>,
+    /// Path or url of the school logo.
+    pub logo: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq, ToSchema)]
+/// Extra information about a problem, not available in BOCA.
+pub struct ProblemMetadata {
+    /// Balloon colour, as a CSS colour.
+    pub color: Option<String>,
+    /// Problem title.
+    pub title: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq, ToSchema)]
+/// Metadata of the teams, by login, and of the problems, by letter.
+pub struct Metadata {
+    #[serde(default)]
+    pub teams: HashMap<String, TeamMetadata>,
+    #[serde(default)]
+    pub problems: HashMap<String, ProblemMetadata>,
+}
+
+impl Metadata {
+    pub fn team(&self, login: &str) -> Option<&TeamMetadata> {
+        self.teams.get(login)
+    }
+
+    pub fn problem(&self, letter: &Letter) -> Option<&ProblemMetadata> {
+        self.problems.get(&letter.to_string())
+    }
+}
+
+impl TeamMetadata {
+    /// The flag emoji of the country, built from its regional indicator symbols.
+    pub fn flag(&self) -> Option<String> {
+        let country = self.country.as_ref()?;
+        if country.len() != 2 || !country.chars().all(|c| c.is_ascii_alphabetic()) {
+            return None;
+        }
+
+        country
+            .to_ascii_uppercase()
+            .chars()
+            .map(|c| char::from_u32(0x1F1E6 + (c as u32 - 'A' as u32)))
+            .collect()
+    }
 }
 
 impl ConfigContest {
@@ -151,17 +256,29 @@ impl ConfigContest {
                 .iter()
                 .map(|(name, entry)| (name.clone(), entry.into_sede()))
                 .collect(),
+            scoring: self.scoring,
         }
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Contest {
     pub sedes: HashMap<String, Sede>,
     pub titulo: Sede,
+    /// Scoring policy of the config, if it overrides the one of the contest.
+    pub scoring: Option<ScoringPolicy>,
 }
 
 impl Contest {
+    /// The teams of `sede` in the contest, scored as the config says.
+    pub fn contest_file(&self, contest: ContestFile, sede: &Sede) -> ContestFile {
+        let mut contest = contest.filter_sede(sede);
+        if let Some(scoring) = self.scoring {
+            contest.scoring = scoring;
+        }
+        contest
+    }
+
     pub fn get_sede_nome_sede(&self, name: &str) -> Option<&Sede> {
         if self.titulo.entry.name == name {
             return Some(&self.titulo);
@@ -170,43 +287,157 @@ impl Contest {
     }
 }
 
-#[derive(Deserialize, Serialize, Debug)]
+#[derive(Deserialize, Serialize, Debug, Clone, Default)]
+/// The secret of the revealer of a sede.
 pub struct SedeSecret {
     pub name: String,
-    pub secret: String,
+    /// The secret in plain text, from old files. Prefer `hash`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub secret: Option<String>,
+    /// The `hash_token` of the secret.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hash: Option<String>,
+    /// Unix time, in seconds, after which the secret is refused.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expires: Option<u64>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+/// What a token allows, besides revealing a sede.
+pub enum Role {
+    /// Sends the contest state to the server.
+    Uploader,
+    /// Overrides runs and reloads the config.
+    Admin,
+    /// Sends commands to the remote controlled screens.
+    RemoteControl,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct RoleToken {
+    /// Who holds the token, for the logs.
+    pub name: String,
+    pub role: Role,
+    /// The `hash_token` of the token.
+    pub hash: String,
+    /// Unix time, in seconds, after which the token is refused.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expires: Option<u64>,
 }
 
 #[derive(Serialize, Deserialize, Debug, Default)]
 pub struct ConfigSecret {
     pub secrets: Vec<SedeSecret>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tokens: Vec<RoleToken>,
+}
+
+/// Tokens are kept hashed, so the secrets files do not hold them.
+/// They are long and random, so a fast hash is enough.
+pub fn hash_token(token: &str) -> String {
+    use sha2::{Digest, Sha256};
+    hex::encode(Sha256::digest(token.as_bytes()))
+}
+
+pub fn unix_now() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+#[derive(Debug, Clone)]
+struct Grant<T> {
+    value: T,
+    expires: Option<u64>,
+}
+
+impl<T> Grant<T> {
+    fn valid(&self) -> Option<&T> {
+        match self.expires {
+            Some(expires) if expires <= unix_now() => None,
+            _ => Some(&self.value),
+        }
+    }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Default)]
 pub struct Secret {
-    /// A map where the key is a shared secret, and the sede is a contest site
-    pub sedes_by_secret: HashMap<String, Sede>,
+    /// The sedes, by the hash of the secret of their revealers.
+    sedes_by_hash: HashMap<String, Grant<Sede>>,
+    roles_by_hash: HashMap<String, Grant<Role>>,
 }
 
 impl Secret {
     pub fn get_sede_by_secret(&self, key: &str) -> Option<&Sede> {
-        self.sedes_by_secret.get(key)
+        self.sedes_by_hash.get(&hash_token(key))?.valid()
+    }
+
+    pub fn has_role(&self, token: &str, role: Role) -> bool {
+        self.roles_by_hash
+            .get(&hash_token(token))
+            .and_then(Grant::valid)
+            .is_some_and(|r| *r == role)
+    }
+
+    /// Whether any token of this role is configured.
+    pub fn defines_role(&self, role: Role) -> bool {
+        self.roles_by_hash.values().any(|grant| grant.value == role)
+    }
+
+    /// The sedes that have revealers.
+    pub fn sedes(&self) -> impl Iterator<Item = &Sede> {
+        self.sedes_by_hash.values().map(|grant| &grant.value)
     }
 }
 
 impl ConfigSecret {
+    /// The salt is prepended to the secrets in plain text.
     pub fn into_secret(&self, salt: Option<String>, sedes: &Contest) -> Secret {
         let salt = salt.unwrap_or_default();
-        let sedes_by_secret = self
+        let sedes_by_hash = self
             .secrets
             .iter()
             .filter_map(|sede_secret| {
-                let complete = format!("{}{}", salt, &sede_secret.secret);
-                sedes
-                    .get_sede_nome_sede(&sede_secret.name)
-                    .map(|sede| (complete, sede.clone()))
+                let hash = match (&sede_secret.hash, &sede_secret.secret) {
+                    (Some(hash), _) => hash.clone(),
+                    (None, Some(secret)) => {
+                        tracing::warn!(sede = sede_secret.name, "secret in plain text");
+                        hash_token(&format!("{salt}{secret}"))
+                    }
+                    (None, None) => return None,
+                };
+                sedes.get_sede_nome_sede(&sede_secret.name).map(|sede| {
+                    (
+                        hash,
+                        Grant {
+                            value: sede.clone(),
+                            expires: sede_secret.expires,
+                        },
+                    )
+                })
+            })
+            .collect();
+
+        let roles_by_hash = self
+            .tokens
+            .iter()
+            .map(|token| {
+                (
+                    token.hash.clone(),
+                    Grant {
+                        value: token.role,
+                        expires: token.expires,
+                    },
+                )
             })
             .collect();
-        Secret { sedes_by_secret }
+
+        Secret {
+            sedes_by_hash,
+            roles_by_hash,
+        }
     }
 }
 
@@ -230,14 +461,22 @@ mod tests {
                 name: "dummy".to_string(),
                 ..SedeEntry::default()
             },
+            scoring: None,
+            metadata_file: None,
+            metadata: None,
+            boca_url: None,
+            event_feed: None,
+            poll: None,
         };
         let contest = config_contest.into_contest();
 
         let config_secret = ConfigSecret {
             secrets: vec![SedeSecret {
                 name: "sede-name".into(),
-                secret: "key".into(),
+                secret: Some("key".into()),
+                ..SedeSecret::default()
             }],
+            tokens: vec![],
         };
         let secret = config_secret.into_secret(None, &contest);
 
@@ -334,4 +573,93 @@ mod tests {
                 .team_belongs_str("$teamag$")
         );
     }
+
+    #[test]
+    fn test_hashed_tokens() {
+        let contest = ConfigContest {
+            sedes: None,
+            titulo: SedeEntry {
+                name: "Brasil".to_string(),
+                ..SedeEntry::default()
+            },
+            scoring: None,
+            metadata_file: None,
+            metadata: None,
+            boca_url: None,
+            event_feed: None,
+            poll: None,
+        }
+        .into_contest();
+
+        let config_secret: ConfigSecret = serde_json::from_value(json!({
+            "secrets": [
+                {"name": "Brasil", "hash": hash_token("revela")},
+                {"name": "Brasil", "hash": hash_token("expirado"), "expires": 1},
+            ],
+            "tokens": [
+                {"name": "boca", "role": "uploader", "hash": hash_token("envia")},
+            ],
+        }))
+        .unwrap();
+        let secret = config_secret.into_secret(None, &contest);
+
+        assert!(secret.get_sede_by_secret("revela").is_some());
+        assert!(secret.get_sede_by_secret(&hash_token("revela")).is_none());
+        assert!(secret.get_sede_by_secret("expirado").is_none());
+
+        assert!(secret.has_role("envia", Role::Uploader));
+        assert!(!secret.has_role("envia", Role::Admin));
+        assert!(!secret.has_role("revela", Role::Uploader));
+        assert!(!secret.defines_role(Role::RemoteControl));
+    }
+
+    #[test]
+    fn test_metadata() {
+        let metadata: Metadata = serde_json::from_value(json!({
+            "teams": {"teambrsc1": {"country": "br", "short_school": "UFSC"}},
+            "problems": {"A": {"color": "#ff0000", "title": "Alfa"}},
+        }))
+        .unwrap();
+
+        let team = metadata.team("teambrsc1").unwrap();
+        assert_eq!(team.short_school.as_deref(), Some("UFSC"));
+        assert_eq!(team.flag().as_deref(), Some("🇧🇷"));
+        assert_eq!(metadata.team("teambrsc2"), None);
+
+        let problem = metadata.problem(&"A".parse().unwrap()).unwrap();
+        assert_eq!(problem.color.as_deref(), Some("#ff0000"));
+    }
+
+    #[test]
+    fn test_source_is_not_sent_to_clients() {
+        let config: ConfigContest = serde_json::from_value(json!({
+            "titulo": {"name": "Fase 1", "codes": [""]},
+            "boca_url": "https://boca/webcast/secret-key",
+        }))
+        .unwrap();
+        assert_eq!(
+            config.boca_url.as_deref(),
+            Some("https://boca/webcast/secret-key")
+        );
+
+        let sent = serde_json::to_value(&config).unwrap();
+        assert!(sent.get("boca_url").is_none());
+        assert!(sent.get("event_feed").is_none());
+    }
+
+    #[test]
+    fn test_poll_backoff() {
+        let config: ConfigContest = serde_json::from_value(json!({
+            "titulo": {"name": "Fase 1", "codes": [""]},
+            "poll": {"interval_ms": 500},
+        }))
+        .unwrap();
+        let poll = config.poll.unwrap();
+        assert_eq!(poll.max_backoff_ms, 30_000);
+
+        assert_eq!(poll.delay(0).as_millis(), 500);
+        assert_eq!(poll.delay(1).as_millis(), 1000);
+        assert_eq!(poll.delay(3).as_millis(), 4000);
+        assert_eq!(poll.delay(100).as_millis(), 30_000);
+    }
 }
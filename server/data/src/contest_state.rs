@@ -8,3 +8,19 @@ pub struct ContestState {
     pub time: TimeFile,
     pub contest: ContestFile,
 }
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+/// Runs that are new or changed since the update with sequence `base`.
+pub struct ContestDelta {
+    pub base: u64,
+    pub sequence: u64,
+    pub time: TimeFile,
+    pub runs: Vec<RunTuple>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+/// The sequence of the last update applied by the server.
+/// `None` when the server needs a full update.
+pub struct UpdateAck {
+    pub sequence: Option<u64>,
+}
@@ -1,11 +1,18 @@
 pub mod annotate_first_solved;
+pub mod balloons;
 pub mod configdata;
 pub mod contest_state;
 pub mod remote_control;
 pub mod revelation;
+pub mod scoring;
+pub mod sequenced;
+#[cfg(any(test, feature = "test-util"))]
+pub mod test_util;
+pub mod wire;
 
 use configdata::Sede;
 use itertools::Itertools;
+use scoring::{ScoringPolicy, ScoringRules};
 use serde::{Deserialize, Serialize};
 use std::cmp::{Eq, Ordering};
 use std::collections::{BTreeMap, HashSet, btree_map};
@@ -115,6 +122,9 @@ pub struct TimerData {
     pub current_time: TimeFile,
     /// Scoreboard freeze time.
     pub score_freeze_time: TimeFile,
+    /// The source has been silent for too long, the data may be out of date.
+    #[serde(default)]
+    pub stale: bool,
 }
 
 impl TimerData {
@@ -122,6 +132,7 @@ impl TimerData {
         Self {
             current_time,
             score_freeze_time,
+            stale: false,
         }
     }
 
@@ -170,7 +181,7 @@ impl Problem {
         }
     }
 
-    fn add_run_problem(&mut self, answer: Answer) {
+    fn add_run_problem(&mut self, answer: Answer, rules: &ScoringRules) {
         self.id = gen_id();
         if self.solved {
             return;
@@ -192,7 +203,7 @@ impl Problem {
             }
             Answer::No { run_id } => {
                 self.submissions += 1;
-                self.penalty += 20;
+                self.penalty += rules.rejected_penalty();
                 self.waits.remove(&run_id);
             }
             Answer::Wait { run_id } => {
@@ -215,11 +226,11 @@ impl Problem {
         }
     }
 
-    fn reveal_run_frozen(&mut self) -> bool {
+    fn reveal_run_frozen(&mut self, rules: &ScoringRules) -> bool {
         self.id = gen_id();
         if self.wait() {
             let a = self.answers.remove(0);
-            self.add_run_problem(a);
+            self.add_run_problem(a, rules);
             // if !self.wait() {
             //     self.answers.clear();
             // }
@@ -254,18 +265,13 @@ impl PartialEq for Team {
     }
 }
 
-impl PartialOrd for Team {
-    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        self.score().partial_cmp(&other.score())
-    }
-}
-
 #[derive(PartialEq, Eq, Debug, Clone)]
 pub struct Score {
     pub solved: usize,
     pub penalty: i64,
     pub max_solution_time: i64,
     pub team_login: String,
+    pub policy: ScoringPolicy,
 }
 
 impl PartialOrd for Score {
@@ -276,15 +282,7 @@ impl PartialOrd for Score {
 
 impl Ord for Score {
     fn cmp(&self, other: &Self) -> Ordering {
-        if self.solved != other.solved {
-            other.solved.cmp(&self.solved)
-        } else if self.penalty != other.penalty {
-            self.penalty.cmp(&other.penalty)
-        } else if self.max_solution_time != other.max_solution_time {
-            self.max_solution_time.cmp(&other.max_solution_time)
-        } else {
-            self.team_login.cmp(&other.team_login)
-        }
+        self.policy.compare(self, other)
     }
 }
 
@@ -312,12 +310,12 @@ impl Team {
         Self::new("<login>", "<escola>", "<nome>".to_string())
     }
 
-    fn apply_run(&mut self, run: &RunTuple) {
+    fn apply_run(&mut self, run: &RunTuple, rules: &ScoringRules) {
         self.id = gen_id();
         self.problems
             .entry(run.prob.clone())
             .or_insert(Problem::empty())
-            .add_run_problem(run.answer.clone());
+            .add_run_problem(run.answer.clone(), rules);
     }
 
     fn apply_run_frozen(&mut self, run: &RunTuple) {
@@ -332,9 +330,9 @@ impl Team {
         self.problems.values().any(|p| p.wait())
     }
 
-    pub fn reveal_run_frozen(&mut self) -> bool {
+    pub fn reveal_run_frozen(&mut self, rules: &ScoringRules) -> bool {
         for p in self.problems.values_mut() {
-            if p.wait() && p.reveal_run_frozen() {
+            if p.wait() && p.reveal_run_frozen(rules) {
                 self.id = gen_id();
                 return true;
             }
@@ -342,7 +340,7 @@ impl Team {
         false
     }
 
-    pub fn score(&self) -> Score {
+    pub fn score(&self, policy: ScoringPolicy) -> Score {
         let mut solved = 0;
         let mut penalty = 0;
         let mut max_solution_time = 0;
@@ -358,6 +356,7 @@ impl Team {
             penalty,
             max_solution_time,
             team_login: self.login.clone(),
+            policy,
         }
     }
 }
@@ -379,6 +378,9 @@ pub struct ContestFile {
     pub penalty_per_wrong_answer: i64,
     /// Number of problems in the contest.
     pub number_problems: usize,
+    /// How the teams are scored and ranked.
+    #[serde(default)]
+    pub scoring: ScoringPolicy,
 }
 
 #[derive(Debug, Hash, PartialEq, Eq, Clone, Serialize, Deserialize, ToSchema)]
@@ -483,9 +485,14 @@ impl ContestFile {
             score_freeze_time,
             penalty_per_wrong_answer: penalty,
             number_problems,
+            scoring: ScoringPolicy::default(),
         }
     }
 
+    pub fn scoring_rules(&self) -> ScoringRules {
+        ScoringRules::new(self.scoring, self.penalty_per_wrong_answer)
+    }
+
     pub fn remove_ccl(self) -> Self {
         Self {
             teams: self
@@ -509,8 +516,9 @@ impl ContestFile {
     }
 
     pub fn recalculate_placement(&mut self) {
+        let policy = self.scoring;
         let mut teams = self.teams.iter_mut().map(|(_t, v)| v).collect::<Vec<_>>();
-        teams.sort_by_cached_key(|t| t.score());
+        teams.sort_by_cached_key(|t| t.score(policy));
 
         for (i, t) in teams.iter_mut().enumerate() {
             if t.placement_global != i + 1 {
@@ -525,8 +533,9 @@ impl ContestFile {
     }
 
     pub fn apply_run(&mut self, r: &RunTuple) {
+        let rules = self.scoring_rules();
         if let Some(t) = self.teams.get_mut(&r.team_login) {
-            t.apply_run(r);
+            t.apply_run(r, &rules);
         }
     }
 
@@ -610,7 +619,7 @@ pub struct RunsPanelItem {
     pub problem_view: ProblemView,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct RunsFile {
     runs: BTreeMap<i64, RunTuple>,
 }
@@ -647,6 +656,10 @@ impl RunsFile {
         self.runs.is_empty()
     }
 
+    pub fn get(&self, run_id: i64) -> Option<&RunTuple> {
+        self.runs.get(&run_id)
+    }
+
     pub fn sorted(&self) -> Vec<RunTuple> {
         let mut r: Vec<_> = self.runs.values().cloned().collect();
         r.sort_by_key(|r| (r.time, r.order));
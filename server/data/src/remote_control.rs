@@ -23,3 +23,11 @@ pub enum ControlMessage {
     QueryString(QueryString),
     PhotoState(PhotoState),
 }
+
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
+/// A control message with its position in the stream, so polling screens resume from it.
+/// Not a [`crate::sequenced::Sequenced`], as the messages cannot be flattened.
+pub struct SequencedControl {
+    pub seq: u64,
+    pub message: ControlMessage,
+}
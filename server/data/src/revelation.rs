@@ -1,14 +1,58 @@
 use annotate_first_solved::annotate_first_solved;
 
 use crate::*;
+use configdata::Color;
 
-use std::collections::{BinaryHeap, HashSet};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeSet, HashMap, HashSet};
 
 #[derive(Debug, Clone)]
 struct Revelation {
     contest: ContestFile,
     runs: RunsFileContest,
     runs_queue: RunsQueue,
+    ranking: Ranking,
+    /// One entry for every run applied from the queue, so steps can be undone.
+    undo_log: Vec<UndoStep>,
+}
+
+#[derive(Debug, Clone)]
+/// What is needed to undo one step of the revelation.
+struct UndoStep {
+    /// Size of the queue before the step.
+    queue_len: usize,
+    /// `None` if the queue was already empty.
+    popped: Option<PoppedRun>,
+}
+
+#[derive(Debug, Clone)]
+struct PoppedRun {
+    score: Score,
+    /// The score the team was pushed back with, if it revealed a run.
+    pushed_back: Option<Score>,
+    /// The problem with the frozen run revealed by the step, as it was before the step.
+    problem: Option<(Letter, Problem)>,
+    team_id: u64,
+    placement_global: usize,
+}
+
+#[derive(Debug, Clone, Copy)]
+/// What the last step of the revelation did.
+pub struct Reveal<'a> {
+    /// The global placement of the team before the step.
+    pub placement_before: usize,
+    /// The team after the step.
+    pub team: &'a Team,
+    /// The problem and the answer revealed. `None` if the team had no frozen runs left.
+    pub revealed: Option<(&'a Letter, &'a Answer)>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+/// The revelation reached a medal zone:
+/// the teams still in the queue are the ones placed up to `placement`.
+pub struct AwardMoment {
+    pub medal: Color,
+    pub placement: usize,
 }
 
 #[derive(Debug)]
@@ -16,6 +60,8 @@ pub struct RevelationDriver {
     starting_point: Revelation,
     revelation: Revelation,
     step: u32,
+    /// Sorted from the worst to the best placement.
+    awards: Vec<AwardMoment>,
 }
 
 impl RevelationDriver {
@@ -28,13 +74,72 @@ impl RevelationDriver {
             starting_point: revelation.clone(),
             revelation,
             step: 0,
+            awards: Vec::new(),
+        }
+    }
+
+    /// What the last step revealed. `None` at the start, or if the queue was empty.
+    pub fn last_reveal(&self) -> Option<Reveal<'_>> {
+        let popped = self.revelation.undo_log.last()?.popped.as_ref()?;
+        let team = self
+            .revelation
+            .contest
+            .teams
+            .get(&popped.score.team_login)?;
+
+        Some(Reveal {
+            placement_before: popped.placement_global,
+            team,
+            revealed: popped
+                .problem
+                .as_ref()
+                .and_then(|(letter, problem)| Some((letter, problem.answers.first()?))),
+        })
+    }
+
+    /// Stops the revelation at the medal cut-offs of `sede`.
+    pub fn with_awards(mut self, sede: &Sede) -> Self {
+        let entry = &sede.entry;
+        self.awards = [entry.bronze, entry.prata, entry.ouro]
+            .into_iter()
+            .filter(|&placement| placement > 0)
+            .sorted_by(|a, b| b.cmp(a))
+            .dedup()
+            .filter_map(|placement| {
+                sede.premio(placement)
+                    .map(|medal| AwardMoment { medal, placement })
+            })
+            .collect();
+        self
+    }
+
+    /// The award moment reached by the last step, if there is one.
+    pub fn award_moment(&self) -> Option<&AwardMoment> {
+        let len = self.len();
+        let reached = self
+            .revelation
+            .undo_log
+            .last()
+            .is_some_and(|undo| undo.queue_len > len);
+
+        self.awards
+            .iter()
+            .find(|award| reached && award.placement == len)
+    }
+
+    /// Reveals runs until the next award moment.
+    /// Does nothing if there are no award moments left.
+    pub fn next_award(&mut self) -> Result<(), ContestError> {
+        let len = self.len();
+        match self.awards.iter().find(|award| award.placement < len) {
+            Some(award) => self.reveal_top_n(award.placement),
+            None => Ok(()),
         }
     }
 
     pub fn reveal_step(&mut self) {
         self.revelation.apply_one_run_from_queue();
         self.step += 1;
-        self.revelation.contest.recalculate_placement()
     }
 
     pub fn peek(&self) -> Option<&String> {
@@ -53,7 +158,6 @@ impl RevelationDriver {
                 self.revelation.apply_one_run_from_queue();
                 self.step += 1;
             }
-            self.revelation.contest.recalculate_placement();
         }
     }
 
@@ -61,6 +165,10 @@ impl RevelationDriver {
         &self.revelation.contest
     }
 
+    pub fn step(&self) -> u32 {
+        self.step
+    }
+
     pub fn len(&self) -> usize {
         self.revelation.runs_queue.len()
     }
@@ -76,12 +184,175 @@ impl RevelationDriver {
 
     pub fn back_one(&mut self) {
         if self.step > 0 {
-            self.revelation = self.starting_point.clone();
+            self.revelation.undo_one_run();
             self.step -= 1;
-            for _ in 0..self.step {
-                self.revelation.apply_one_run_from_queue();
+        }
+    }
+
+    /// Goes to the step where only the top `n` teams are left in the queue,
+    /// the same as a `restart` followed by `reveal_top_n`,
+    /// but moving from the current step instead of replaying from the start.
+    pub fn jump_to_top_n(&mut self, n: usize) -> Result<(), ContestError> {
+        while self
+            .revelation
+            .undo_log
+            .last()
+            .is_some_and(|undo| undo.queue_len <= n)
+        {
+            self.revelation.undo_one_run();
+            self.step -= 1;
+        }
+        self.reveal_top_n(n)
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+/// A command of the revelation ceremony.
+pub enum RevelationCommand {
+    StepForward,
+    StepBack,
+    JumpTeamForward,
+    JumpTeamBack,
+    TopN(usize),
+    RevealAll,
+    Reset,
+    NextAward,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+/// What the viewers of a revelation should render.
+pub struct RevelationView {
+    pub step: u32,
+    /// The team under the spotlight, once the ceremony has started.
+    pub center: Option<String>,
+    pub award: Option<AwardMoment>,
+    pub contest: ContestFile,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+/// A step of the revelation, with only the teams that changed since the previous step.
+pub struct RevelationDelta {
+    pub step: u32,
+    pub center: Option<String>,
+    pub award: Option<AwardMoment>,
+    /// The teams with a new score, or a new placement.
+    pub teams: Vec<Team>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+/// What a revelation session sends to its viewers.
+pub enum RevelationUpdate {
+    /// The whole view, when a viewer connects, or falls behind.
+    Full(RevelationView),
+    Delta(RevelationDelta),
+}
+
+#[derive(Debug, Default)]
+/// The teams as last sent in a [`RevelationDelta`], by login,
+/// with the id of their score and their placements.
+pub struct SentTeams(HashMap<String, (u64, usize, usize)>);
+
+#[derive(Debug)]
+/// A revelation ceremony, driven by [`RevelationCommand`]s.
+pub struct RevelationState {
+    is_started: bool,
+    driver: RevelationDriver,
+}
+
+impl RevelationState {
+    pub fn new(contest: ContestFile, runs: RunsFile, sede: &Sede) -> Self {
+        Self {
+            is_started: false,
+            driver: RevelationDriver::new(contest, runs).with_awards(sede),
+        }
+    }
+
+    pub fn apply(&mut self, command: RevelationCommand) -> Result<(), ContestError> {
+        match command {
+            RevelationCommand::StepForward => {
+                if self.is_started && !self.driver.is_empty() {
+                    self.driver.reveal_step();
+                } else {
+                    self.is_started = true;
+                }
+            }
+            RevelationCommand::StepBack => {
+                self.is_started = true;
+                self.driver.back_one();
+            }
+            RevelationCommand::JumpTeamForward => {
+                if self.is_started {
+                    self.driver.jump_team_forward();
+                } else {
+                    self.is_started = true
+                }
+            }
+            RevelationCommand::JumpTeamBack => {
+                let n = self.driver.len();
+                return self.apply(RevelationCommand::TopN(n + 1));
+            }
+            RevelationCommand::TopN(n) => {
+                self.is_started = true;
+                self.driver.jump_to_top_n(n)?;
+            }
+            RevelationCommand::RevealAll => {
+                self.driver.reveal_top_n(0)?;
+                self.is_started = false;
+            }
+            RevelationCommand::Reset => {
+                self.is_started = false;
+                self.driver.restart();
+            }
+            RevelationCommand::NextAward => {
+                self.is_started = true;
+                self.driver.next_award()?;
             }
-            self.revelation.contest.recalculate_placement();
+        }
+        Ok(())
+    }
+
+    pub fn center(&self) -> Option<&String> {
+        self.is_started.then(|| self.driver.peek()).flatten()
+    }
+
+    pub fn contest(&self) -> &ContestFile {
+        self.driver.contest()
+    }
+
+    pub fn award_moment(&self) -> Option<&AwardMoment> {
+        self.is_started
+            .then(|| self.driver.award_moment())
+            .flatten()
+    }
+
+    pub fn view(&self) -> RevelationView {
+        RevelationView {
+            step: self.driver.step(),
+            center: self.center().cloned(),
+            award: self.award_moment().cloned(),
+            contest: self.driver.contest().clone(),
+        }
+    }
+
+    /// The step, with the teams that changed since they were last `sent`, which is updated.
+    pub fn delta(&self, sent: &mut SentTeams) -> RevelationDelta {
+        let teams = self
+            .driver
+            .contest()
+            .teams
+            .values()
+            .filter(|team| {
+                let version = (team.id, team.placement, team.placement_global);
+                sent.0.insert(team.login.clone(), version) != Some(version)
+            })
+            .cloned()
+            .collect();
+
+        RevelationDelta {
+            step: self.driver.step(),
+            center: self.center().cloned(),
+            award: self.award_moment().cloned(),
+            teams,
         }
     }
 }
@@ -95,6 +366,8 @@ impl Revelation {
             contest,
             runs,
             runs_queue: RunsQueue::empty(),
+            ranking: Ranking::default(),
+            undo_log: Vec::new(),
         }
     }
 
@@ -107,11 +380,40 @@ impl Revelation {
             }
         }
         self.runs_queue = RunsQueue::setup_queue(&self.contest);
-        self.contest.recalculate_placement()
+        self.contest.recalculate_placement();
+        self.ranking = Ranking::new(&self.contest);
     }
 
     fn apply_one_run_from_queue(&mut self) {
-        self.runs_queue.pop_run(&mut self.contest);
+        let queue_len = self.runs_queue.len();
+        let popped = self.runs_queue.pop_run(&mut self.contest);
+        if let Some(PoppedRun {
+            score,
+            pushed_back: Some(pushed_back),
+            ..
+        }) = &popped
+        {
+            self.ranking
+                .update(&mut self.contest, score, pushed_back.clone());
+        }
+        self.undo_log.push(UndoStep { queue_len, popped });
+    }
+
+    fn undo_one_run(&mut self) {
+        if let Some(UndoStep {
+            popped: Some(popped),
+            ..
+        }) = self.undo_log.pop()
+        {
+            let moved = popped
+                .pushed_back
+                .clone()
+                .map(|pushed_back| (pushed_back, popped.score.clone()));
+            self.runs_queue.unpop_run(popped, &mut self.contest);
+            if let Some((pushed_back, score)) = moved {
+                self.ranking.update(&mut self.contest, &pushed_back, score);
+            }
+        }
     }
 
     fn apply_runs_from_queue_n(&mut self, n: usize) -> Result<u32, ContestError> {
@@ -120,20 +422,68 @@ impl Revelation {
             count += 1;
             self.apply_one_run_from_queue();
         }
-        self.contest.recalculate_placement();
         Ok(count)
     }
 }
 
+#[derive(Debug, Clone, Default)]
+/// The scores of all teams, from the best to the worst placed,
+/// so a team that changes its score only moves the teams it passes.
+struct Ranking(Vec<Score>);
+
+impl Ranking {
+    fn new(contest: &ContestFile) -> Self {
+        Self(
+            contest
+                .teams
+                .values()
+                .map(|team| team.score(contest.scoring))
+                .sorted()
+                .collect(),
+        )
+    }
+
+    /// Moves a team from its score `before` to its score `after`,
+    /// updating the global placements of the teams in between.
+    fn update(&mut self, contest: &mut ContestFile, before: &Score, after: Score) {
+        let Ok(from) = self.0.binary_search(before) else {
+            return;
+        };
+        self.0[from] = after;
+
+        let mut to = from;
+        while to > 0 && self.0[to] < self.0[to - 1] {
+            self.0.swap(to, to - 1);
+            to -= 1;
+        }
+        while to + 1 < self.0.len() && self.0[to] > self.0[to + 1] {
+            self.0.swap(to, to + 1);
+            to += 1;
+        }
+
+        for i in from.min(to)..=from.max(to) {
+            if let Some(team) = contest
+                .teams
+                .get_mut(&self.0[i].team_login)
+                .filter(|team| team.placement_global != i + 1)
+            {
+                team.placement_global = i + 1;
+                team.id = gen_id();
+            }
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
+/// The teams left to reveal. The last one is the worst placed.
 struct RunsQueue {
-    queue: BinaryHeap<Score>,
+    queue: BTreeSet<Score>,
 }
 
 impl RunsQueue {
     fn empty() -> Self {
         Self {
-            queue: BinaryHeap::new(),
+            queue: BTreeSet::new(),
         }
     }
 
@@ -146,29 +496,312 @@ impl RunsQueue {
     }
 
     fn peek(&self) -> Option<&String> {
-        self.queue.peek().map(|s| &s.team_login)
+        self.queue.last().map(|s| &s.team_login)
     }
 
     fn setup_queue(contest: &ContestFile) -> Self {
         let mut q = Self::empty();
         for team in contest.teams.values() {
-            q.queue.push(team.score())
+            q.queue.insert(team.score(contest.scoring));
         }
         q
     }
 
-    fn pop_run(&mut self, contest: &mut ContestFile) {
-        let entry = self.queue.pop();
+    fn pop_run(&mut self, contest: &mut ContestFile) -> Option<PoppedRun> {
+        let rules = contest.scoring_rules();
+        let entry = self.queue.pop_last();
         match entry {
-            None => (),
+            None => None,
             Some(score) => match contest.teams.get_mut(&score.team_login) {
                 None => panic!("invalid team!"),
                 Some(team) => {
-                    if team.reveal_run_frozen() {
-                        self.queue.push(team.score());
+                    let team_id = team.id;
+                    let problem = team
+                        .problems
+                        .iter()
+                        .find(|(_, problem)| problem.wait())
+                        .map(|(letter, problem)| (letter.clone(), problem.clone()));
+                    let pushed_back = team
+                        .reveal_run_frozen(&rules)
+                        .then(|| team.score(rules.policy));
+                    if let Some(pushed_back) = &pushed_back {
+                        self.queue.insert(pushed_back.clone());
                     }
+                    Some(PoppedRun {
+                        score,
+                        pushed_back,
+                        problem,
+                        team_id,
+                        placement_global: team.placement_global,
+                    })
                 }
             },
         }
     }
+    /// Reverts a `pop_run`. Only the last popped run can be reverted.
+    fn unpop_run(&mut self, popped: PoppedRun, contest: &mut ContestFile) {
+        let PoppedRun {
+            score,
+            pushed_back,
+            problem,
+            team_id,
+            ..
+        } = popped;
+
+        if let Some(pushed_back) = pushed_back {
+            self.queue.remove(&pushed_back);
+        }
+        if let Some(team) = contest.teams.get_mut(&score.team_login) {
+            if let Some((letter, problem)) = problem {
+                team.problems.insert(letter, problem);
+            }
+            team.id = team_id;
+        }
+        self.queue.insert(score);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        scoring::ScoringPolicy,
+        test_util::{run, yes},
+    };
+
+    fn state_with(logins: &[&str], runs: Vec<RunTuple>, medals: [usize; 3]) -> RevelationState {
+        let teams = logins
+            .iter()
+            .map(|login| Team::new(login, "escola", login.to_string()))
+            .collect();
+        let contest = ContestFile::new("c".to_string(), teams, 0, 300, 240, 20, 1);
+
+        let [ouro, prata, bronze] = medals;
+        let sede = configdata::SedeEntry {
+            name: "sede".to_string(),
+            ouro,
+            prata,
+            bronze,
+            ..Default::default()
+        }
+        .into_sede();
+
+        RevelationState::new(contest, RunsFile::new(runs), &sede)
+    }
+
+    fn state() -> RevelationState {
+        state_with(
+            &["team1", "team2"],
+            vec![
+                run(1, "team1", 250, yes(1, 250)),
+                run(2, "team2", 250, yes(2, 250)),
+            ],
+            [1, 1, 1],
+        )
+    }
+
+    #[test]
+    fn test_award_moments() -> Result<(), ContestError> {
+        let gold = AwardMoment {
+            medal: Color::Gold,
+            placement: 1,
+        };
+
+        let mut stepped = state();
+        for _ in 0..3 {
+            stepped.apply(RevelationCommand::StepForward)?;
+            assert_eq!(stepped.award_moment(), None);
+        }
+        stepped.apply(RevelationCommand::StepForward)?;
+        assert_eq!(stepped.award_moment(), Some(&gold));
+
+        let mut skipped = state();
+        skipped.apply(RevelationCommand::NextAward)?;
+        assert_eq!(skipped.award_moment(), Some(&gold));
+        assert_eq!(skipped.view().step, stepped.view().step);
+
+        skipped.apply(RevelationCommand::StepBack)?;
+        assert_eq!(skipped.award_moment(), None);
+        Ok(())
+    }
+
+    #[test]
+    fn test_award_moments_by_medal() -> Result<(), ContestError> {
+        let state = || {
+            state_with(
+                &["team1", "team2", "team3", "team4"],
+                vec![
+                    run(1, "team1", 250, yes(1, 250)),
+                    run(2, "team2", 260, yes(2, 260)),
+                    run(3, "team3", 270, Answer::No { run_id: 3 }),
+                    run(4, "team4", 280, yes(4, 280)),
+                ],
+                [1, 2, 3],
+            )
+        };
+        let moments = [(Color::Bronze, 3), (Color::Silver, 2), (Color::Gold, 1)]
+            .map(|(medal, placement)| AwardMoment { medal, placement });
+
+        let mut stepped = state();
+        stepped.apply(RevelationCommand::StepForward)?;
+        let mut reached = vec![];
+        while !stepped.driver.is_empty() {
+            stepped.apply(RevelationCommand::StepForward)?;
+            if let Some(award) = stepped.award_moment() {
+                reached.push((award.clone(), stepped.view().step));
+            }
+        }
+        assert_eq!(
+            reached.iter().map(|(award, _)| award).collect::<Vec<_>>(),
+            moments.iter().collect::<Vec<_>>()
+        );
+
+        let mut skipped = state();
+        for (award, step) in &reached {
+            skipped.apply(RevelationCommand::NextAward)?;
+            assert_eq!(skipped.award_moment(), Some(award));
+            assert_eq!(skipped.view().step, *step);
+            assert_eq!(skipped.driver.len(), award.placement);
+        }
+
+        skipped.apply(RevelationCommand::NextAward)?;
+        assert_eq!(skipped.award_moment(), Some(&moments[2]));
+        assert_eq!(skipped.view().step, reached[2].1);
+        Ok(())
+    }
+
+    #[test]
+    fn test_first_step_only_starts() -> Result<(), ContestError> {
+        let mut state = state();
+        assert_eq!(state.center(), None);
+
+        state.apply(RevelationCommand::StepForward)?;
+        assert_eq!(state.view().step, 0);
+        assert!(state.center().is_some());
+
+        state.apply(RevelationCommand::StepForward)?;
+        assert_eq!(state.view().step, 1);
+
+        state.apply(RevelationCommand::StepBack)?;
+        assert_eq!(state.view().step, 0);
+        Ok(())
+    }
+
+    #[test]
+    fn test_reveal_all_and_reset() -> Result<(), ContestError> {
+        let mut state = state();
+
+        state.apply(RevelationCommand::RevealAll)?;
+        assert_eq!(state.center(), None);
+        assert!(
+            state
+                .contest()
+                .teams
+                .values()
+                .all(|t| t.score(ScoringPolicy::Icpc).solved == 1)
+        );
+
+        state.apply(RevelationCommand::Reset)?;
+        assert_eq!(state.view().step, 0);
+        assert!(
+            state
+                .contest()
+                .teams
+                .values()
+                .all(|t| t.score(ScoringPolicy::Icpc).solved == 0)
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_delta_has_changed_teams() -> Result<(), ContestError> {
+        let mut state = state();
+        let mut sent = SentTeams::default();
+        assert_eq!(state.delta(&mut sent).teams.len(), 2);
+        assert!(state.delta(&mut sent).teams.is_empty());
+
+        state.apply(RevelationCommand::StepForward)?;
+        assert!(state.delta(&mut sent).teams.is_empty());
+
+        state.apply(RevelationCommand::StepForward)?;
+        let delta = state.delta(&mut sent);
+        assert_eq!(delta.step, 1);
+        assert!(!delta.teams.is_empty());
+        assert!(delta.teams.len() <= 2);
+
+        state.apply(RevelationCommand::StepBack)?;
+        let reverted = state.delta(&mut sent);
+        assert_eq!(
+            reverted.teams.iter().map(|t| &t.login).collect::<Vec<_>>(),
+            delta.teams.iter().map(|t| &t.login).collect::<Vec<_>>()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_jump_to_top_n_matches_replay() -> Result<(), ContestError> {
+        let scores = |state: &RevelationState| {
+            state
+                .contest()
+                .teams
+                .values()
+                .map(|t| (t.placement_global, t.score(ScoringPolicy::Icpc)))
+                .collect::<Vec<_>>()
+        };
+
+        let mut replayed = state();
+        replayed.apply(RevelationCommand::TopN(1))?;
+
+        let mut jumped = state();
+        jumped.apply(RevelationCommand::RevealAll)?;
+        jumped.apply(RevelationCommand::TopN(1))?;
+
+        assert_eq!(jumped.view().step, replayed.view().step);
+        assert_eq!(jumped.center(), replayed.center());
+        assert_eq!(scores(&jumped), scores(&replayed));
+        Ok(())
+    }
+
+    #[test]
+    fn test_placements_follow_each_step() -> Result<(), ContestError> {
+        let placements = |contest: &ContestFile| {
+            contest
+                .teams
+                .values()
+                .map(|t| (t.login.clone(), t.placement_global))
+                .sorted()
+                .collect::<Vec<_>>()
+        };
+        let recalculated = |state: &RevelationState| {
+            let mut contest = state.contest().clone();
+            contest.recalculate_placement();
+            placements(&contest)
+        };
+
+        let mut state = state_with(
+            &["team1", "team2", "team3", "team4"],
+            vec![
+                run(1, "team4", 100, yes(1, 100)),
+                run(2, "team3", 245, Answer::No { run_id: 2 }),
+                run(3, "team1", 250, yes(3, 250)),
+                run(4, "team2", 260, yes(4, 260)),
+                run(5, "team3", 270, yes(5, 270)),
+            ],
+            [1, 2, 3],
+        );
+        state.apply(RevelationCommand::StepForward)?;
+
+        let mut seen = vec![placements(state.contest())];
+        while !state.driver.is_empty() {
+            state.apply(RevelationCommand::StepForward)?;
+            assert_eq!(placements(state.contest()), recalculated(&state));
+            seen.push(placements(state.contest()));
+        }
+
+        while let Some(expected) = seen.pop() {
+            assert_eq!(placements(state.contest()), expected);
+            state.apply(RevelationCommand::StepBack)?;
+        }
+        Ok(())
+    }
 }
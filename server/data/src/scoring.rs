@@ -0,0 +1,139 @@
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+use utoipa::ToSchema;
+
+use crate::Score;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+/// How runs are turned into a score, and how scores are ranked.
+pub enum ScoringPolicy {
+    /// ICPC rules: more solved problems first, then less penalty.
+    /// The penalty of a solved problem is the time it was solved plus
+    /// `penalty_per_wrong_answer` for every rejected run before it.
+    #[default]
+    Icpc,
+    /// Rejected runs are free.
+    /// More solved problems first, then the earliest last accepted run,
+    /// then the sum of the solving times.
+    NoPenalty,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// The scoring policy of a contest, together with its parameters.
+pub struct ScoringRules {
+    pub policy: ScoringPolicy,
+    pub penalty_per_wrong_answer: i64,
+}
+
+impl ScoringRules {
+    pub fn new(policy: ScoringPolicy, penalty_per_wrong_answer: i64) -> Self {
+        Self {
+            policy,
+            penalty_per_wrong_answer,
+        }
+    }
+
+    /// Penalty added to a problem when a run is rejected.
+    pub fn rejected_penalty(&self) -> i64 {
+        match self.policy {
+            ScoringPolicy::Icpc => self.penalty_per_wrong_answer,
+            ScoringPolicy::NoPenalty => 0,
+        }
+    }
+}
+
+impl ScoringPolicy {
+    /// Compares two scores. Lesser scores are better placed.
+    pub fn compare(&self, a: &Score, b: &Score) -> Ordering {
+        let by_solved = b.solved.cmp(&a.solved);
+        let by_penalty = a.penalty.cmp(&b.penalty);
+        let by_last_solution = a.max_solution_time.cmp(&b.max_solution_time);
+        let by_login = a.team_login.cmp(&b.team_login);
+
+        match self {
+            ScoringPolicy::Icpc => by_solved.then(by_penalty).then(by_last_solution),
+            ScoringPolicy::NoPenalty => by_solved.then(by_last_solution).then(by_penalty),
+        }
+        .then(by_login)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        Answer, ContestFile, Team,
+        test_util::{run, yes},
+    };
+
+    fn score(solved: usize, penalty: i64, max_solution_time: i64, login: &str) -> Score {
+        Score {
+            solved,
+            penalty,
+            max_solution_time,
+            team_login: login.to_string(),
+            policy: ScoringPolicy::Icpc,
+        }
+    }
+
+    #[test]
+    fn test_icpc_ranks_by_penalty_before_last_solution() {
+        let a = score(2, 100, 90, "a");
+        let b = score(2, 110, 60, "b");
+
+        assert_eq!(ScoringPolicy::Icpc.compare(&a, &b), Ordering::Less);
+        assert_eq!(ScoringPolicy::NoPenalty.compare(&a, &b), Ordering::Greater);
+    }
+
+    #[test]
+    fn test_more_solved_always_wins() {
+        let a = score(3, 900, 290, "a");
+        let b = score(2, 10, 5, "b");
+
+        assert_eq!(ScoringPolicy::Icpc.compare(&a, &b), Ordering::Less);
+        assert_eq!(ScoringPolicy::NoPenalty.compare(&a, &b), Ordering::Less);
+    }
+
+    #[test]
+    fn test_rejected_penalty() {
+        assert_eq!(
+            ScoringRules::new(ScoringPolicy::Icpc, 15).rejected_penalty(),
+            15
+        );
+        assert_eq!(
+            ScoringRules::new(ScoringPolicy::NoPenalty, 15).rejected_penalty(),
+            0
+        );
+    }
+
+    #[test]
+    fn test_contest_penalty_per_wrong_answer() {
+        let team = Team::new("team1", "escola", "team".to_string());
+        let mut contest = ContestFile::new("c".to_string(), vec![team], 0, 300, 240, 15, 1);
+
+        contest.apply_run(&run(1, "team1", 10, Answer::No { run_id: 1 }));
+        contest.apply_run(&run(2, "team1", 30, yes(2, 30)));
+
+        let icpc = contest.teams["team1"].score(contest.scoring);
+        assert_eq!(icpc.penalty, 45);
+
+        let mut contest = ContestFile {
+            scoring: ScoringPolicy::NoPenalty,
+            ..ContestFile::new(
+                "c".to_string(),
+                vec![Team::new("team1", "escola", "team".to_string())],
+                0,
+                300,
+                240,
+                15,
+                1,
+            )
+        };
+        contest.apply_run(&run(1, "team1", 10, Answer::No { run_id: 1 }));
+        contest.apply_run(&run(2, "team1", 30, yes(2, 30)));
+
+        let no_penalty = contest.teams["team1"].score(contest.scoring);
+        assert_eq!(no_penalty.penalty, 30);
+    }
+}
@@ -0,0 +1,12 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+/// A value broadcast by the server, with its position in the stream.
+///
+/// Sequences only grow, so a client can resume the stream from the last one it received.
+pub struct Sequenced<T> {
+    #[serde(default)]
+    pub seq: u64,
+    #[serde(flatten)]
+    pub value: T,
+}
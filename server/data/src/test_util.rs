@@ -0,0 +1,24 @@
+//! Fixtures for the tests of this crate, and of the crates that enable `test-util`.
+
+use crate::{Answer, RunTuple};
+
+/// A run of problem `A`, in the order of its id.
+pub fn run(id: i64, team_login: &str, time: i64, answer: Answer) -> RunTuple {
+    RunTuple {
+        id,
+        order: id as u64,
+        time,
+        team_login: team_login.to_string(),
+        prob: "A".parse().unwrap(),
+        answer,
+    }
+}
+
+/// Accepted at `time`, not the first to solve the problem.
+pub fn yes(run_id: i64, time: i64) -> Answer {
+    Answer::Yes {
+        time,
+        is_first: false,
+        run_id,
+    }
+}
@@ -0,0 +1,113 @@
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
+use thiserror::Error;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+/// Encoding of the messages sent to the clients.
+/// JSON is the default, MessagePack is smaller, for setups with many clients on a weak uplink.
+pub enum WireFormat {
+    #[default]
+    Json,
+    Msgpack,
+}
+
+#[derive(Debug, Error)]
+pub enum WireError {
+    #[error(transparent)]
+    Json(#[from] serde_json::Error),
+    #[error(transparent)]
+    Encode(#[from] rmp_serde::encode::Error),
+    #[error(transparent)]
+    Decode(#[from] rmp_serde::decode::Error),
+}
+
+pub const MSGPACK_CONTENT_TYPE: &str = "application/msgpack";
+
+impl WireFormat {
+    /// The format asked for in an `Accept` header, JSON unless MessagePack is accepted.
+    pub fn from_accept(accept: &str) -> Self {
+        let msgpack = accept
+            .split(',')
+            .any(|media| media.split(';').next().map(str::trim) == Some(MSGPACK_CONTENT_TYPE));
+        if msgpack {
+            WireFormat::Msgpack
+        } else {
+            WireFormat::Json
+        }
+    }
+
+    /// The format of a response, from its `Content-Type` header.
+    pub fn from_content_type(content_type: &str) -> Self {
+        if content_type.starts_with(MSGPACK_CONTENT_TYPE) {
+            WireFormat::Msgpack
+        } else {
+            WireFormat::Json
+        }
+    }
+
+    /// The name of the format in a query, as in `?format=msgpack`.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            WireFormat::Json => "json",
+            WireFormat::Msgpack => "msgpack",
+        }
+    }
+
+    pub fn content_type(self) -> &'static str {
+        match self {
+            WireFormat::Json => "application/json",
+            WireFormat::Msgpack => MSGPACK_CONTENT_TYPE,
+        }
+    }
+
+    pub fn encode<T: Serialize>(self, value: &T) -> Result<Vec<u8>, WireError> {
+        Ok(match self {
+            WireFormat::Json => serde_json::to_vec(value)?,
+            // Fields are sent by name, so flattened and skipped fields work as in JSON.
+            WireFormat::Msgpack => rmp_serde::to_vec_named(value)?,
+        })
+    }
+
+    pub fn decode<T: DeserializeOwned>(self, bytes: &[u8]) -> Result<T, WireError> {
+        Ok(match self {
+            WireFormat::Json => serde_json::from_slice(bytes)?,
+            WireFormat::Msgpack => rmp_serde::from_slice(bytes)?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        RunTuple,
+        sequenced::Sequenced,
+        test_util::{run, yes},
+    };
+
+    use super::*;
+
+    #[test]
+    fn test_msgpack_round_trip() {
+        let run = Sequenced {
+            seq: 42,
+            value: run(7, "teambrsc1", 120, yes(7, 120)),
+        };
+
+        let json = WireFormat::Json.encode(&run).unwrap();
+        let msgpack = WireFormat::Msgpack.encode(&run).unwrap();
+        assert!(msgpack.len() < json.len());
+
+        let decoded: Sequenced<RunTuple> = WireFormat::Msgpack.decode(&msgpack).unwrap();
+        assert_eq!(decoded.seq, run.seq);
+        assert_eq!(decoded.value, run.value);
+    }
+
+    #[test]
+    fn test_from_accept() {
+        assert_eq!(
+            WireFormat::from_accept("application/msgpack;q=1.0, application/json"),
+            WireFormat::Msgpack
+        );
+        assert_eq!(WireFormat::from_accept("*/*"), WireFormat::Json);
+    }
+}
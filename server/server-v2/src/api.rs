@@ -1,10 +1,15 @@
 use actix_web::*;
 use actix_ws::Closed;
 use autometrics::autometrics;
-use serde::Deserialize;
+use data::wire::{WireError, WireFormat};
+use serde::{Deserialize, Serialize};
 use tracing::{Level, debug, warn};
 
-use crate::{app_data::AppData, endpoints};
+use crate::{
+    app_data::{AppData, bearer_token},
+    connections::IdleWatch,
+    endpoints,
+};
 
 #[derive(Deserialize, Debug)]
 struct ContestQuery {
@@ -19,36 +24,111 @@ pub fn configure(cfg: &mut web::ServiceConfig) {
         get_allruns_ws,
         get_allruns_secret,
         endpoints::update_contest::update_contest,
+        endpoints::update_contest::update_contest_delta,
     ));
+    cfg.service((
+        endpoints::clics::get_scoreboard,
+        endpoints::clics::get_contest,
+        endpoints::clics::get_judgement_types,
+        endpoints::clics::get_problems,
+        endpoints::clics::get_teams,
+        endpoints::clics::get_submissions,
+        endpoints::clics::get_judgements,
+        endpoints::clics::get_event_feed,
+    ));
+    cfg.service((
+        endpoints::balloons::get_balloons,
+        endpoints::balloons::put_balloon,
+    ));
+    cfg.service((
+        endpoints::admin_runs::get_runs,
+        endpoints::admin_runs::put_run_override,
+        endpoints::admin_runs::get_audit,
+    ));
+    cfg.service((
+        endpoints::streams::get_allruns_sse,
+        endpoints::streams::get_timer_sse,
+        endpoints::streams::get_allruns_poll,
+        endpoints::streams::get_timer_poll,
+        endpoints::streams::get_remote_control_sse,
+        endpoints::streams::get_remote_control_poll,
+    ));
+    cfg.service((
+        endpoints::status::get_status,
+        endpoints::status::get_health,
+        endpoints::status::get_ready,
+    ));
+}
+
+/// The format asked for in the `Accept` header, JSON by default.
+fn accepted_format(req: &HttpRequest) -> WireFormat {
+    req.headers()
+        .get(http::header::ACCEPT)
+        .and_then(|accept| accept.to_str().ok())
+        .map(WireFormat::from_accept)
+        .unwrap_or_default()
+}
+
+/// Answers in the format asked for in the `Accept` header, JSON by default.
+fn negotiated(req: &HttpRequest, value: &impl Serialize) -> HttpResponse {
+    let format = accepted_format(req);
+    encoded(format, format.encode(value).map(web::Bytes::from))
+}
+
+fn encoded(format: WireFormat, body: Result<web::Bytes, WireError>) -> HttpResponse {
+    match body {
+        Ok(body) => HttpResponse::Ok()
+            .content_type(format.content_type())
+            .insert_header((http::header::VARY, "Accept"))
+            .body(body),
+        Err(err) => {
+            warn!(?err, "failed encoding response");
+            HttpResponse::InternalServerError().finish()
+        }
+    }
 }
 
 #[get("/contest")]
 async fn get_contest(
     data: web::Data<AppData>,
     contest: web::Query<ContestQuery>,
+    req: HttpRequest,
 ) -> impl Responder {
     get_contest_fn(
         data,
         contest.into_inner().contest.unwrap_or_default().as_str(),
+        req,
     )
     .await
 }
 
-#[tracing::instrument(level = Level::DEBUG, skip(data), ret)]
+#[tracing::instrument(level = Level::DEBUG, skip(data, req), ret)]
 #[autometrics]
-async fn get_contest_fn(data: web::Data<AppData>, sede_config: &str) -> impl Responder + use<> {
-    let db = data.shared_db.lock().await;
-    if db.time_file < 0 {
+async fn get_contest_fn(
+    data: web::Data<AppData>,
+    sede_config: &str,
+    req: HttpRequest,
+) -> impl Responder + use<> {
+    let Some(host) = data.host(sede_config) else {
+        return HttpResponse::NotFound().finish();
+    };
+    let view = host.view.load();
+    if view.time_file < 0 {
         return HttpResponse::Forbidden().finish();
     }
 
-    match data.config.get(sede_config) {
-        Some((_, contest, _)) => {
-            let result = db.contest_file_begin.clone().filter_sede(&contest.titulo);
-            HttpResponse::Ok().json(result)
-        }
-        None => HttpResponse::NotFound().finish(),
-    }
+    let configs = data.config.load();
+    let Some((_, contest, _)) = configs.get(sede_config) else {
+        return HttpResponse::NotFound().finish();
+    };
+
+    let format = accepted_format(&req);
+    let body = host
+        .contest_cache
+        .get_or_build(sede_config, format, view.version, &configs, || {
+            format.encode(&contest.contest_file(view.contest_file_begin.clone(), &contest.titulo))
+        });
+    encoded(format, body)
 }
 
 #[get("/config")]
@@ -63,12 +143,14 @@ async fn get_config(data: web::Data<AppData>, contest: web::Query<ContestQuery>)
 #[tracing::instrument(level = Level::DEBUG, skip(data), ret)]
 #[autometrics]
 async fn get_config_fn(data: web::Data<AppData>, sede_config: &str) -> impl Responder + use<> {
-    let db = data.shared_db.lock().await;
-    if db.time_file < 0 {
+    let Some(host) = data.host(sede_config) else {
+        return HttpResponse::NotFound().finish();
+    };
+    if host.view.load().time_file < 0 {
         return HttpResponse::Forbidden().finish();
     }
 
-    match data.config.get(sede_config) {
+    match data.config.load().get(sede_config) {
         Some((config, _, _)) => HttpResponse::Ok().json(config),
         None => HttpResponse::NotFound().finish(),
     }
@@ -76,29 +158,36 @@ async fn get_config_fn(data: web::Data<AppData>, sede_config: &str) -> impl Resp
 
 #[derive(Debug, Deserialize)]
 struct SecretQuery {
-    secret: String,
+    /// The secret of the revealer, when it is not sent as a bearer token.
+    secret: Option<String>,
 }
 
-#[tracing::instrument(level = Level::DEBUG, skip(data), ret)]
+#[tracing::instrument(level = Level::DEBUG, skip(data, req), ret)]
 #[autometrics]
 async fn get_allruns_secret_fn(
     data: web::Data<AppData>,
     sede_config: &str,
     query: web::Query<SecretQuery>,
+    req: HttpRequest,
 ) -> impl Responder + use<> {
     let sede = data
         .config
+        .load()
         .get(sede_config)
-        .and_then(|(_, _, s)| s.get_sede_by_secret(&query.secret).cloned());
+        .zip(bearer_token(&req).or(query.secret.as_deref()))
+        .and_then(|((_, _, s), key)| s.get_sede_by_secret(key).cloned());
 
+    let Some(host) = data.host(sede_config) else {
+        return HttpResponse::NotFound().finish();
+    };
     match sede {
         None => HttpResponse::Forbidden().finish(),
         Some(sede) => {
-            let db = data.shared_db.lock().await;
-            if db.time_file < 0 {
+            let view = host.view.load();
+            if view.time_file < 0 {
                 HttpResponse::Forbidden().finish()
             } else {
-                HttpResponse::Ok().json(db.run_file_secret.filter_sede(&sede))
+                negotiated(&req, &view.run_file_secret.filter_sede(&sede))
             }
         }
     }
@@ -109,25 +198,40 @@ async fn get_allruns_secret(
     data: web::Data<AppData>,
     query: web::Query<SecretQuery>,
     contest: web::Query<ContestQuery>,
+    req: HttpRequest,
 ) -> impl Responder {
     get_allruns_secret_fn(
         data,
         contest.into_inner().contest.unwrap_or_default().as_str(),
         query,
+        req,
     )
     .await
 }
 
+#[derive(Debug, Deserialize)]
+struct SinceQuery {
+    /// Last sequence received, to resume the stream after a reconnect.
+    since: Option<u64>,
+    /// Websockets cannot send an `Accept` header, so the format is asked for in the query.
+    #[serde(default)]
+    format: WireFormat,
+}
+
 #[get("/allruns_ws")]
 async fn get_allruns_ws(
     data: web::Data<AppData>,
     req: HttpRequest,
     body: web::Payload,
     contest: web::Query<ContestQuery>,
+    since: web::Query<SinceQuery>,
 ) -> Result<HttpResponse, Error> {
+    let since = since.into_inner();
     get_allruns_ws_fn(
         data,
         contest.into_inner().contest.unwrap_or_default().as_str(),
+        since.since,
+        since.format,
         req,
         body,
     )
@@ -139,14 +243,25 @@ async fn get_allruns_ws(
 async fn get_allruns_ws_fn(
     data: web::Data<AppData>,
     sede_config: &str,
+    since: Option<u64>,
+    format: WireFormat,
     req: HttpRequest,
     body: web::Payload,
 ) -> Result<HttpResponse, Error> {
-    let (response, mut session, _msg_stream) = actix_ws::handle(&req, body)?;
-    let mut runs_rx = data.runs_tx.subscribe();
+    let Some(host) = data.host(sede_config) else {
+        return Ok(HttpResponse::NotFound().finish());
+    };
+    let permit = match data.connections.acquire(&req) {
+        Ok(permit) => permit,
+        Err(rejected) => return Ok(rejected),
+    };
+    let (response, mut session, msg_stream) = actix_ws::handle(&req, body)?;
+    let mut idle = IdleWatch::new(session.clone(), msg_stream, data.connections.idle_timeout());
+    let mut runs_rx = host.runs_tx.subscribe_since(since);
 
     let sede = data
         .config
+        .load()
         .get(sede_config)
         .map(|(_config, contest, _secret)| contest.titulo.clone());
 
@@ -154,13 +269,32 @@ async fn get_allruns_ws_fn(
         None => Ok(HttpResponse::Forbidden().finish()),
         Some(sede) => {
             actix_web::rt::spawn(async move {
+                let _permit = permit;
                 loop {
-                    match runs_rx.recv().await {
+                    let next = tokio::select! {
+                        next = runs_rx.recv() => next,
+                        () = idle.closed() => {
+                            debug!("ws connection idle or closed");
+                            break;
+                        }
+                    };
+                    match next {
                         Ok(r) => {
-                            if sede.team_belongs_str(&r.team_login) {
-                                match serde_json::to_string(&r) {
-                                    Ok(text) => {
-                                        if let Err(Closed) = session.text(text).await {
+                            if sede.team_belongs_str(&r.value.team_login) {
+                                match format.encode(&r) {
+                                    Ok(bytes) => {
+                                        let sent = match format {
+                                            WireFormat::Json => {
+                                                session
+                                                    .text(
+                                                        String::from_utf8_lossy(&bytes)
+                                                            .into_owned(),
+                                                    )
+                                                    .await
+                                            }
+                                            WireFormat::Msgpack => session.binary(bytes).await,
+                                        };
+                                        if let Err(Closed) = sent {
                                             debug!("ws connection closed");
                                             break;
                                         }
@@ -175,6 +309,7 @@ async fn get_allruns_ws_fn(
                         }
                     }
                 }
+                let _ = session.close(None).await;
             });
             Ok(response)
         }
@@ -186,24 +321,48 @@ async fn get_timer(
     data: web::Data<AppData>,
     req: HttpRequest,
     body: web::Payload,
+    contest: web::Query<ContestQuery>,
 ) -> Result<HttpResponse, Error> {
-    get_timer_fn(data, req, body).await
+    get_timer_fn(
+        data,
+        contest.into_inner().contest.unwrap_or_default().as_str(),
+        req,
+        body,
+    )
+    .await
 }
 
 #[autometrics]
 #[tracing::instrument(level = Level::DEBUG, skip(data, body), ret)]
 async fn get_timer_fn(
     data: web::Data<AppData>,
+    sede_config: &str,
     req: HttpRequest,
     body: web::Payload,
 ) -> Result<HttpResponse, Error> {
-    let (response, mut session, _msg_stream) = actix_ws::handle(&req, body)?;
-    let mut time_rx = data.time_tx.subscribe();
+    let Some(host) = data.host(sede_config) else {
+        return Ok(HttpResponse::NotFound().finish());
+    };
+    let permit = match data.connections.acquire(&req) {
+        Ok(permit) => permit,
+        Err(rejected) => return Ok(rejected),
+    };
+    let (response, mut session, msg_stream) = actix_ws::handle(&req, body)?;
+    let mut idle = IdleWatch::new(session.clone(), msg_stream, data.connections.idle_timeout());
+    let mut time_rx = host.time_tx.subscribe();
 
     actix_web::rt::spawn(async move {
+        let _permit = permit;
         let mut previous = None;
         loop {
-            match time_rx.recv().await {
+            let next = tokio::select! {
+                next = time_rx.recv() => next,
+                () = idle.closed() => {
+                    debug!("ws connection idle or closed");
+                    break;
+                }
+            };
+            match next {
                 Ok(time) => {
                     if previous.is_some_and(|x| x == time) {
                         continue;
@@ -220,12 +379,15 @@ async fn get_timer_fn(
                         Err(err) => warn!(?err, "failed serializing time"),
                     }
                 }
+                // Only the last timer matters.
+                Err(tokio::sync::broadcast::error::RecvError::Lagged(_)) => continue,
                 Err(err) => {
                     warn!(?err, "recv failed");
                     break;
                 }
             }
         }
+        let _ = session.close(None).await;
     });
 
     Ok(response)
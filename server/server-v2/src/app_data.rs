@@ -1,19 +1,68 @@
 use std::{collections::HashMap, sync::Arc};
 
-use data::{
-    RunTuple, TimerData,
-    configdata::{ConfigContest, Contest, Secret},
+use actix_web::{HttpRequest, http::header};
+use data::configdata::Role;
+use service::{app_config::ConfigLoader, snapshot::SnapshotStore};
+use tokio::sync::Mutex;
+
+use crate::{
+    config_reload::SharedConfig, connections::ConnectionLimiter, contest_host::ContestHost,
+    endpoints::balloons::DeliveredBalloons, remote_control, revelation_session::RevelationSessions,
 };
-use service::{DB, membroadcast};
-use tokio::sync::{Mutex, broadcast};
 
-use crate::remote_control;
+/// The token of the request, sent as `Authorization: Bearer <token>`.
+pub fn bearer_token(req: &HttpRequest) -> Option<&str> {
+    req.headers()
+        .get(header::AUTHORIZATION)?
+        .to_str()
+        .ok()?
+        .strip_prefix("Bearer ")
+        .map(str::trim)
+}
 
 pub struct AppData {
-    pub shared_db: Arc<Mutex<DB>>,
-    pub runs_tx: membroadcast::Sender<RunTuple>,
-    pub time_tx: broadcast::Sender<TimerData>,
-    pub config: Arc<HashMap<String, (ConfigContest, Contest, Secret)>>,
+    /// The host of each configured contest.
+    /// Contests without a source of their own share one host,
+    /// read from the main source if there is one, or updated by the uploader.
+    pub hosts: Arc<HashMap<String, ContestHost>>,
+    pub config: Arc<SharedConfig>,
+    pub config_loader: Option<ConfigLoader>,
     pub remote_control: Arc<Mutex<HashMap<String, remote_control::ControlSender>>>,
-    pub server_api_key: Option<String>,
+    pub revelation_sessions: RevelationSessions,
+    pub delivered_balloons: Arc<Mutex<DeliveredBalloons>>,
+    pub snapshot_store: Option<SnapshotStore>,
+    pub connections: Arc<ConnectionLimiter>,
+}
+
+impl AppData {
+    /// The host of the contest `name`, if it is configured.
+    pub fn host(&self, name: &str) -> Option<&ContestHost> {
+        self.hosts.get(name)
+    }
+
+    /// Whether the request carries a valid token of the role.
+    /// Tokens come from the secrets files, shared by all contests.
+    pub fn authorize(&self, req: &HttpRequest, role: Role) -> bool {
+        bearer_token(req).is_some_and(|token| self.has_role(token, role))
+    }
+
+    pub fn has_role(&self, token: &str, role: Role) -> bool {
+        let authorized = self
+            .config
+            .load()
+            .values()
+            .any(|(_, _, secret)| secret.has_role(token, role));
+        if !authorized {
+            tracing::warn!(?role, "refused token");
+        }
+        authorized
+    }
+
+    /// Whether any token of the role is configured.
+    pub fn defines_role(&self, role: Role) -> bool {
+        self.config
+            .load()
+            .values()
+            .any(|(_, _, secret)| secret.defines_role(role))
+    }
 }
@@ -0,0 +1,137 @@
+use std::sync::{Arc, RwLock};
+
+use actix_web::{HttpRequest, HttpResponse, Responder, get, post, web};
+use actix_ws::Closed;
+use autometrics::autometrics;
+use data::configdata::Role;
+use service::app_config::ConfigMap;
+use tokio::sync::broadcast::{self, Sender};
+use tracing::{Level, debug, warn};
+
+use crate::{app_data::AppData, connections::IdleWatch};
+
+/// The contest configs, swapped as a whole when they are reloaded.
+pub struct SharedConfig {
+    current: RwLock<(u64, Arc<ConfigMap>)>,
+    updates: Sender<u64>,
+}
+
+impl SharedConfig {
+    pub fn new(config: ConfigMap) -> Self {
+        let (updates, _) = broadcast::channel(16);
+        Self {
+            current: RwLock::new((0, Arc::new(config))),
+            updates,
+        }
+    }
+
+    /// The current configs. Requests keep the configs they started with.
+    pub fn load(&self) -> Arc<ConfigMap> {
+        self.current.read().expect("config lock poisoned").1.clone()
+    }
+
+    /// Replaces the configs and tells the clients to fetch them again.
+    fn swap(&self, config: ConfigMap) -> u64 {
+        let version = {
+            let mut current = self.current.write().expect("config lock poisoned");
+            *current = (current.0 + 1, Arc::new(config));
+            current.0
+        };
+
+        // No clients is not an error.
+        let _ = self.updates.send(version);
+        version
+    }
+}
+
+#[post("/config/reload")]
+pub async fn reload_config(data: web::Data<AppData>, req: HttpRequest) -> impl Responder {
+    reload_config_fn(data, req).await
+}
+
+#[tracing::instrument(level = Level::DEBUG, skip(data, req), ret)]
+#[autometrics]
+async fn reload_config_fn(data: web::Data<AppData>, req: HttpRequest) -> HttpResponse {
+    if !data.authorize(&req, Role::Admin) {
+        return HttpResponse::Unauthorized().finish();
+    }
+
+    let Some(loader) = &data.config_loader else {
+        return HttpResponse::NotImplemented().finish();
+    };
+
+    let config = match loader() {
+        Ok(config) => config,
+        Err(err) => {
+            warn!(?err, "rejected config reload");
+            return HttpResponse::BadRequest().body(err.to_string());
+        }
+    };
+
+    for name in config.keys() {
+        if !data.hosts.contains_key(name) {
+            warn!(
+                ?name,
+                "contests and their sources are only read at startup, restart to add them"
+            );
+        }
+    }
+
+    let version = data.config.swap(config);
+    tracing::info!(version, "reloaded config");
+    HttpResponse::Ok().json(version)
+}
+
+#[get("/config_ws")]
+pub async fn get_config_ws(
+    data: web::Data<AppData>,
+    req: HttpRequest,
+    body: web::Payload,
+) -> Result<HttpResponse, actix_web::Error> {
+    get_config_ws_fn(data, req, body).await
+}
+
+#[autometrics]
+#[tracing::instrument(level = Level::DEBUG, skip(data, body), ret)]
+async fn get_config_ws_fn(
+    data: web::Data<AppData>,
+    req: HttpRequest,
+    body: web::Payload,
+) -> Result<HttpResponse, actix_web::Error> {
+    let permit = match data.connections.acquire(&req) {
+        Ok(permit) => permit,
+        Err(rejected) => return Ok(rejected),
+    };
+    let (response, mut session, msg_stream) = actix_ws::handle(&req, body)?;
+    let mut idle = IdleWatch::new(session.clone(), msg_stream, data.connections.idle_timeout());
+    let mut updates = data.config.updates.subscribe();
+
+    actix_web::rt::spawn(async move {
+        let _permit = permit;
+        loop {
+            let next = tokio::select! {
+                next = updates.recv() => next,
+                () = idle.closed() => {
+                    debug!("ws connection idle or closed");
+                    break;
+                }
+            };
+            match next {
+                Ok(version) => {
+                    if let Err(Closed) = session.text(version.to_string()).await {
+                        debug!("ws connection closed");
+                        break;
+                    }
+                }
+                Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                Err(err) => {
+                    warn!(?err, "recv failed");
+                    break;
+                }
+            }
+        }
+        let _ = session.close(None).await;
+    });
+
+    Ok(response)
+}
@@ -0,0 +1,247 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use actix_web::{HttpRequest, HttpResponse, http::header};
+use actix_ws::{Message, MessageStream, Session};
+use futures::StreamExt;
+use metrics::{counter, gauge};
+use service::http::ConnectionLimits;
+use tokio::time::Instant;
+
+/// Clients are asked to wait this long before reconnecting.
+const RETRY_AFTER_SECS: u64 = 10;
+
+#[derive(Debug, Default)]
+struct Open {
+    total: usize,
+    by_ip: HashMap<String, usize>,
+}
+
+#[derive(Debug)]
+/// Counts the long lived connections, so a popular scoreboard does not take the server down.
+pub struct ConnectionLimiter {
+    limits: ConnectionLimits,
+    open: Mutex<Open>,
+}
+
+/// A slot of an open connection, released when dropped.
+pub struct ConnectionPermit {
+    limiter: Arc<ConnectionLimiter>,
+    ip: String,
+}
+
+impl ConnectionLimiter {
+    pub fn new(limits: ConnectionLimits) -> Arc<Self> {
+        Arc::new(Self {
+            limits,
+            open: Mutex::new(Open::default()),
+        })
+    }
+
+    pub fn idle_timeout(&self) -> Duration {
+        self.limits.idle_timeout
+    }
+
+    fn client_ip(&self, req: &HttpRequest) -> String {
+        let info = req.connection_info();
+        let ip = if self.limits.trust_forwarded_for {
+            info.realip_remote_addr()
+        } else {
+            info.peer_addr()
+        };
+        ip.unwrap_or_default().to_string()
+    }
+
+    /// Takes a slot for the connection, or the response rejecting it.
+    pub fn acquire(self: &Arc<Self>, req: &HttpRequest) -> Result<ConnectionPermit, HttpResponse> {
+        let ip = self.client_ip(req);
+        let mut open = self.open.lock().unwrap_or_else(|e| e.into_inner());
+
+        let from_ip = open.by_ip.get(&ip).copied().unwrap_or_default();
+        let rejected = if open.total >= self.limits.max_connections {
+            Some(("global", HttpResponse::ServiceUnavailable()))
+        } else if from_ip >= self.limits.max_connections_per_ip {
+            Some(("per_ip", HttpResponse::TooManyRequests()))
+        } else {
+            None
+        };
+
+        if let Some((reason, mut response)) = rejected {
+            counter!("connections_rejected", "reason" => reason).increment(1);
+            tracing::debug!(ip, reason, "rejected connection");
+            return Err(response
+                .insert_header((header::RETRY_AFTER, RETRY_AFTER_SECS.to_string()))
+                .finish());
+        }
+
+        open.total += 1;
+        *open.by_ip.entry(ip.clone()).or_default() += 1;
+        gauge!("connections_open").set(open.total as f64);
+
+        Ok(ConnectionPermit {
+            limiter: self.clone(),
+            ip,
+        })
+    }
+}
+
+impl Drop for ConnectionPermit {
+    fn drop(&mut self) {
+        let mut open = self.limiter.open.lock().unwrap_or_else(|e| e.into_inner());
+        open.total = open.total.saturating_sub(1);
+        if let Some(count) = open.by_ip.get_mut(&self.ip) {
+            *count -= 1;
+            if *count == 0 {
+                open.by_ip.remove(&self.ip);
+            }
+        }
+        gauge!("connections_open").set(open.total as f64);
+    }
+}
+
+/// Pings a websocket, and tells when it stops answering.
+pub struct IdleWatch {
+    session: Session,
+    messages: MessageStream,
+    timeout: Duration,
+    last_seen: Instant,
+    ping: tokio::time::Interval,
+}
+
+impl IdleWatch {
+    pub fn new(session: Session, messages: MessageStream, timeout: Duration) -> Self {
+        Self {
+            session,
+            messages,
+            timeout,
+            last_seen: Instant::now(),
+            ping: tokio::time::interval(timeout / 2),
+        }
+    }
+
+    /// Waits for the client to go away, or to stay silent longer than the timeout.
+    /// Cancel safe, so it can race the messages sent to the client.
+    pub async fn closed(&mut self) {
+        loop {
+            tokio::select! {
+                message = self.messages.next() => match message {
+                    Some(Ok(Message::Close(_))) | Some(Err(_)) | None => return,
+                    Some(Ok(Message::Ping(bytes))) => {
+                        self.last_seen = Instant::now();
+                        if self.session.pong(&bytes).await.is_err() {
+                            return;
+                        }
+                    }
+                    Some(Ok(_)) => self.last_seen = Instant::now(),
+                },
+                _ = self.ping.tick() => {
+                    if self.last_seen.elapsed() > self.timeout {
+                        counter!("connections_idle_closed").increment(1);
+                        return;
+                    }
+                    if self.session.ping(b"").await.is_err() {
+                        return;
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::{FromRequest, http::StatusCode, test::TestRequest, web};
+    use futures::stream;
+
+    use super::*;
+
+    fn limiter(max_connections: usize, max_connections_per_ip: usize) -> Arc<ConnectionLimiter> {
+        ConnectionLimiter::new(ConnectionLimits {
+            max_connections,
+            max_connections_per_ip,
+            ..Default::default()
+        })
+    }
+
+    fn request_from(ip: &str) -> HttpRequest {
+        TestRequest::default()
+            .peer_addr(format!("{ip}:4000").parse().unwrap())
+            .to_http_request()
+    }
+
+    fn total(limiter: &ConnectionLimiter) -> usize {
+        limiter.open.lock().unwrap().total
+    }
+
+    #[test]
+    fn test_rejects_per_ip() {
+        let limiter = limiter(10, 2);
+        let first = limiter.acquire(&request_from("10.0.0.1")).unwrap();
+        let _second = limiter.acquire(&request_from("10.0.0.1")).unwrap();
+
+        let rejected = limiter.acquire(&request_from("10.0.0.1")).err().unwrap();
+        assert_eq!(rejected.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert!(rejected.headers().contains_key(header::RETRY_AFTER));
+        assert!(limiter.acquire(&request_from("10.0.0.2")).is_ok());
+
+        drop(first);
+        assert!(limiter.acquire(&request_from("10.0.0.1")).is_ok());
+    }
+
+    #[test]
+    fn test_rejects_globally() {
+        let limiter = limiter(2, 10);
+        let first = limiter.acquire(&request_from("10.0.0.1")).unwrap();
+        let second = limiter.acquire(&request_from("10.0.0.2")).unwrap();
+        assert_eq!(total(&limiter), 2);
+
+        let rejected = limiter.acquire(&request_from("10.0.0.3")).err().unwrap();
+        assert_eq!(rejected.status(), StatusCode::SERVICE_UNAVAILABLE);
+
+        drop(first);
+        assert_eq!(total(&limiter), 1);
+        let third = limiter.acquire(&request_from("10.0.0.3")).unwrap();
+
+        drop((second, third));
+        assert_eq!(total(&limiter), 0);
+        assert!(limiter.open.lock().unwrap().by_ip.is_empty());
+    }
+
+    async fn websocket(
+        mut payload: actix_web::dev::Payload,
+    ) -> (HttpResponse, Session, MessageStream) {
+        let req = TestRequest::default()
+            .insert_header((header::UPGRADE, "websocket"))
+            .insert_header((header::CONNECTION, "upgrade"))
+            .insert_header((header::SEC_WEBSOCKET_VERSION, "13"))
+            .insert_header((header::SEC_WEBSOCKET_KEY, "dGhlIHNhbXBsZSBub25jZQ=="))
+            .to_http_request();
+        let body = web::Payload::from_request(&req, &mut payload)
+            .await
+            .unwrap();
+        actix_ws::handle(&req, body).unwrap()
+    }
+
+    #[actix_web::test]
+    async fn test_idle_watch_closes() {
+        let timeout = Duration::from_millis(100);
+
+        // The client went away.
+        let (_response, session, messages) = websocket(stream::empty().boxed_local().into()).await;
+        let mut idle = IdleWatch::new(session, messages, timeout);
+        tokio::time::timeout(timeout, idle.closed()).await.unwrap();
+
+        // The client never answers the pings.
+        let (_response, session, messages) =
+            websocket(stream::pending().boxed_local().into()).await;
+        let mut idle = IdleWatch::new(session, messages, timeout);
+        let start = Instant::now();
+        tokio::time::timeout(timeout * 4, idle.closed())
+            .await
+            .unwrap();
+        assert!(start.elapsed() > timeout);
+    }
+}
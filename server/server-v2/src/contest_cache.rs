@@ -0,0 +1,68 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+use actix_web::web::Bytes;
+use data::wire::{WireError, WireFormat};
+use metrics::counter;
+use service::app_config::ConfigMap;
+
+struct Cached {
+    version: u64,
+    config: Arc<ConfigMap>,
+    body: Bytes,
+}
+
+#[derive(Default)]
+/// Pre-serialised `/api/contest` bodies, by contest and format.
+/// A body is built again only when the view of the contest, or the configs, change.
+pub struct ContestCache {
+    bodies: Mutex<HashMap<(String, WireFormat), Cached>>,
+}
+
+impl ContestCache {
+    /// The body built for this version of the view and configs, building it if there is none.
+    pub fn get_or_build(
+        &self,
+        contest: &str,
+        format: WireFormat,
+        version: u64,
+        config: &Arc<ConfigMap>,
+        build: impl FnOnce() -> Result<Vec<u8>, WireError>,
+    ) -> Result<Bytes, WireError> {
+        let key = (contest.to_string(), format);
+
+        if let Some(cached) = self
+            .bodies
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .get(&key)
+            && cached.version == version
+            && Arc::ptr_eq(&cached.config, config)
+        {
+            counter!("contest_cache", "result" => "hit").increment(1);
+            return Ok(cached.body.clone());
+        }
+
+        // Built without the lock, so readers of other contests do not wait for it.
+        counter!("contest_cache", "result" => "miss").increment(1);
+        let body = Bytes::from(build()?);
+
+        let mut bodies = self.bodies.lock().unwrap_or_else(|e| e.into_inner());
+        if bodies
+            .get(&key)
+            .is_none_or(|cached| cached.version <= version)
+        {
+            bodies.insert(
+                key,
+                Cached {
+                    version,
+                    config: config.clone(),
+                    body: body.clone(),
+                },
+            );
+        }
+        Ok(body)
+    }
+}
@@ -0,0 +1,89 @@
+use std::{sync::Arc, time::Duration};
+
+use data::{RunTuple, TimerData};
+use service::{
+    DB, PublishedView,
+    dbupdate_v2::{db_update_loop, staleness_watch},
+    errors::ServiceResult,
+    membroadcast,
+    snapshot::SnapshotStore,
+    source::{Source, SourceHealth},
+};
+use tokio::sync::{Mutex, broadcast};
+
+use crate::contest_cache::ContestCache;
+
+/// Runs a receiver may fall behind before it replays them from the memo.
+const RUNS_CAPACITY: usize = 1024;
+
+/// Timers a receiver may fall behind, it only needs the last one.
+const TIMER_CAPACITY: usize = 16;
+
+#[derive(Clone)]
+/// One contest hosted by the server, with its own DB, channels and timer.
+pub struct ContestHost {
+    pub shared_db: Arc<Mutex<DB>>,
+    pub runs_tx: membroadcast::Sender<RunTuple>,
+    pub time_tx: broadcast::Sender<TimerData>,
+    pub snapshot_store: Option<SnapshotStore>,
+    /// The last view of the DB, for the readers that must not wait for updates.
+    pub view: Arc<PublishedView>,
+    pub contest_cache: Arc<ContestCache>,
+    /// The source read by this host, if it has one.
+    pub source: Option<Source>,
+}
+
+impl ContestHost {
+    /// Restores the contest from its snapshot and starts reading its source, if it has one.
+    /// The contest is stale once it is not updated for `stale_after`.
+    #[tracing::instrument(skip(snapshot_store), err)]
+    pub async fn start(
+        source: Option<Source>,
+        snapshot_store: Option<SnapshotStore>,
+        stale_after: Duration,
+    ) -> ServiceResult<Self> {
+        let (runs_tx, _) = membroadcast::channel(RUNS_CAPACITY);
+        let (time_tx, _) = broadcast::channel(TIMER_CAPACITY);
+
+        let db = match &snapshot_store {
+            Some(store) => {
+                let restored = store.restore(&runs_tx).await?;
+                tracing::info!(dir = ?store.dir(), restored = restored.is_some(), "snapshot store");
+                restored.unwrap_or_else(DB::empty)
+            }
+            None => DB::empty(),
+        };
+        let view = db.view();
+        let shared_db = Arc::new(Mutex::new(db));
+
+        let _staleness = tokio::task::spawn(staleness_watch(
+            shared_db.clone(),
+            time_tx.clone(),
+            stale_after,
+        ));
+
+        if let Some(source) = &source {
+            let _update = tokio::task::spawn(db_update_loop(
+                source.clone(),
+                shared_db.clone(),
+                runs_tx.clone(),
+                time_tx.clone(),
+                snapshot_store.clone(),
+            ));
+        }
+
+        Ok(Self {
+            shared_db,
+            runs_tx,
+            time_tx,
+            snapshot_store,
+            view,
+            contest_cache: Arc::default(),
+            source,
+        })
+    }
+
+    pub fn source_health(&self) -> Option<SourceHealth> {
+        self.source.as_ref().map(Source::health)
+    }
+}
@@ -0,0 +1,152 @@
+use actix_web::{HttpRequest, HttpResponse, Responder, get, put, web};
+use autometrics::autometrics;
+use data::{Letter, RunTuple, configdata::Role};
+use serde::{Deserialize, Serialize};
+use service::{dbupdate_v2::override_run, errors::Error, overrides::RunOverride};
+use tracing::Level;
+
+use crate::app_data::AppData;
+
+#[derive(Debug, Deserialize)]
+pub struct RunsQuery {
+    contest: Option<String>,
+    team: Option<String>,
+    problem: Option<Letter>,
+}
+
+#[derive(Debug, Serialize)]
+struct AdminRun {
+    /// The run as read from the source.
+    #[serde(flatten)]
+    run: RunTuple,
+    #[serde(rename = "override")]
+    run_override: Option<RunOverride>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ContestQuery {
+    contest: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct OverrideRequest {
+    /// The new override, `null` to remove it.
+    #[serde(rename = "override")]
+    run_override: Option<RunOverride>,
+    author: String,
+    reason: String,
+}
+
+#[get("/admin/runs")]
+pub async fn get_runs(
+    data: web::Data<AppData>,
+    query: web::Query<RunsQuery>,
+    req: HttpRequest,
+) -> impl Responder {
+    get_runs_fn(data, query.into_inner(), req).await
+}
+
+#[tracing::instrument(level = Level::DEBUG, skip(data, req))]
+#[autometrics]
+async fn get_runs_fn(data: web::Data<AppData>, query: RunsQuery, req: HttpRequest) -> HttpResponse {
+    if !data.authorize(&req, Role::Admin) {
+        return HttpResponse::Unauthorized().finish();
+    }
+
+    let Some(host) = data.host(query.contest.as_deref().unwrap_or_default()) else {
+        return HttpResponse::NotFound().finish();
+    };
+    let db = host.shared_db.lock().await;
+
+    let runs = db
+        .source_runs()
+        .into_iter()
+        .filter(|(run, _)| query.team.as_ref().is_none_or(|t| &run.team_login == t))
+        .filter(|(run, _)| query.problem.as_ref().is_none_or(|p| &run.prob == p))
+        .map(|(run, run_override)| AdminRun { run, run_override })
+        .collect::<Vec<_>>();
+
+    HttpResponse::Ok().json(runs)
+}
+
+#[put("/admin/runs/{run_id}")]
+pub async fn put_run_override(
+    data: web::Data<AppData>,
+    run_id: web::Path<i64>,
+    query: web::Query<ContestQuery>,
+    request: web::Json<OverrideRequest>,
+    req: HttpRequest,
+) -> impl Responder {
+    put_run_override_fn(
+        data,
+        run_id.into_inner(),
+        query.into_inner(),
+        request.into_inner(),
+        req,
+    )
+    .await
+}
+
+#[tracing::instrument(level = Level::DEBUG, skip(data, req))]
+#[autometrics]
+async fn put_run_override_fn(
+    data: web::Data<AppData>,
+    run_id: i64,
+    query: ContestQuery,
+    request: OverrideRequest,
+    req: HttpRequest,
+) -> HttpResponse {
+    if !data.authorize(&req, Role::Admin) {
+        return HttpResponse::Unauthorized().finish();
+    }
+
+    let Some(host) = data.host(query.contest.as_deref().unwrap_or_default()) else {
+        return HttpResponse::NotFound().finish();
+    };
+    let result = override_run(
+        run_id,
+        request.run_override,
+        request.author,
+        request.reason,
+        &host.shared_db,
+        &host.runs_tx,
+        host.snapshot_store.as_ref(),
+    )
+    .await;
+
+    match result {
+        Ok(()) => HttpResponse::NoContent().finish(),
+        Err(Error::UnknownRun(_)) => HttpResponse::NotFound().finish(),
+        Err(err) => {
+            tracing::error!(?err, "failed overriding run");
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
+
+#[get("/admin/audit")]
+pub async fn get_audit(
+    data: web::Data<AppData>,
+    query: web::Query<ContestQuery>,
+    req: HttpRequest,
+) -> impl Responder {
+    get_audit_fn(data, query.into_inner(), req).await
+}
+
+#[tracing::instrument(level = Level::DEBUG, skip(data, req))]
+#[autometrics]
+async fn get_audit_fn(
+    data: web::Data<AppData>,
+    query: ContestQuery,
+    req: HttpRequest,
+) -> HttpResponse {
+    if !data.authorize(&req, Role::Admin) {
+        return HttpResponse::Unauthorized().finish();
+    }
+
+    let Some(host) = data.host(query.contest.as_deref().unwrap_or_default()) else {
+        return HttpResponse::NotFound().finish();
+    };
+    let db = host.shared_db.lock().await;
+    HttpResponse::Ok().json(db.overrides.audit())
+}
@@ -0,0 +1,158 @@
+use std::collections::{HashMap, HashSet};
+
+use actix_web::{HttpRequest, HttpResponse, Responder, get, put, web};
+use autometrics::autometrics;
+use data::balloons::balloons;
+use serde::Deserialize;
+use service::{errors::ServiceResult, snapshot::SnapshotStore};
+use tracing::Level;
+
+use crate::app_data::{AppData, bearer_token};
+
+const BALLOONS_FILE: &str = "balloons.json";
+
+/// Delivered balloons by contest, as the run ids of the balloons.
+pub type DeliveredBalloons = HashMap<String, HashSet<i64>>;
+
+/// Loads the delivered balloons saved next to the snapshot.
+pub async fn load_delivered(store: Option<&SnapshotStore>) -> ServiceResult<DeliveredBalloons> {
+    match store {
+        Some(store) => Ok(store.load_file(BALLOONS_FILE).await?.unwrap_or_default()),
+        None => Ok(DeliveredBalloons::new()),
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct BalloonsQuery {
+    contest: Option<String>,
+    /// The secret of the sede, when it is not sent as a bearer token.
+    secret: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Delivery {
+    delivered: bool,
+}
+
+#[get("/balloons")]
+pub async fn get_balloons(
+    data: web::Data<AppData>,
+    query: web::Query<BalloonsQuery>,
+    req: HttpRequest,
+) -> impl Responder {
+    get_balloons_fn(data, query.into_inner(), req).await
+}
+
+#[tracing::instrument(level = Level::DEBUG, skip(data, req))]
+#[autometrics]
+async fn get_balloons_fn(
+    data: web::Data<AppData>,
+    query: BalloonsQuery,
+    req: HttpRequest,
+) -> HttpResponse {
+    let contest_name = query.contest.unwrap_or_default();
+    let configs = data.config.load();
+    let Some(sede) = configs
+        .get(&contest_name)
+        .zip(bearer_token(&req).or(query.secret.as_deref()))
+        .and_then(|((_, _, secret), key)| secret.get_sede_by_secret(key))
+    else {
+        return HttpResponse::Forbidden().finish();
+    };
+
+    let Some(host) = data.host(&contest_name) else {
+        return HttpResponse::NotFound().finish();
+    };
+    let db = host.view.load();
+    if db.time_file < 0 {
+        return HttpResponse::Forbidden().finish();
+    }
+
+    let delivered = data.delivered_balloons.lock().await;
+    HttpResponse::Ok().json(balloons(
+        &db.contest_file_begin,
+        &db.run_file_secret,
+        sede,
+        delivered.get(&contest_name).unwrap_or(&HashSet::new()),
+    ))
+}
+
+#[put("/balloons/{run_id}")]
+pub async fn put_balloon(
+    data: web::Data<AppData>,
+    run_id: web::Path<i64>,
+    query: web::Query<BalloonsQuery>,
+    delivery: web::Json<Delivery>,
+    req: HttpRequest,
+) -> impl Responder {
+    put_balloon_fn(
+        data,
+        run_id.into_inner(),
+        query.into_inner(),
+        delivery.into_inner(),
+        req,
+    )
+    .await
+}
+
+#[tracing::instrument(level = Level::DEBUG, skip(data, req))]
+#[autometrics]
+async fn put_balloon_fn(
+    data: web::Data<AppData>,
+    run_id: i64,
+    query: BalloonsQuery,
+    delivery: Delivery,
+    req: HttpRequest,
+) -> HttpResponse {
+    let contest_name = query.contest.unwrap_or_default();
+    let configs = data.config.load();
+    let Some(sede) = configs
+        .get(&contest_name)
+        .zip(bearer_token(&req).or(query.secret.as_deref()))
+        .and_then(|((_, _, secret), key)| secret.get_sede_by_secret(key))
+    else {
+        return HttpResponse::Forbidden().finish();
+    };
+
+    let Some(host) = data.host(&contest_name) else {
+        return HttpResponse::NotFound().finish();
+    };
+
+    {
+        let db = host.view.load();
+        if db.time_file < 0 {
+            return HttpResponse::Forbidden().finish();
+        }
+
+        // Staff of a sede can only deliver the balloons of their own teams.
+        let is_balloon = balloons(
+            &db.contest_file_begin,
+            &db.run_file_secret,
+            sede,
+            &HashSet::new(),
+        )
+        .iter()
+        .any(|balloon| balloon.run_id == run_id);
+
+        if !is_balloon {
+            return HttpResponse::NotFound().finish();
+        }
+    }
+
+    let mut delivered = data.delivered_balloons.lock().await;
+    let contest_delivered = delivered.entry(contest_name).or_default();
+    if delivery.delivered {
+        contest_delivered.insert(run_id);
+    } else {
+        contest_delivered.remove(&run_id);
+    }
+
+    if let Some(store) = &data.snapshot_store
+        && let Err(err) = store.save_file(BALLOONS_FILE, &*delivered).await
+    {
+        tracing::error!(?err, "failed saving delivered balloons");
+        return HttpResponse::InternalServerError().finish();
+    }
+
+    HttpResponse::NoContent().finish()
+}
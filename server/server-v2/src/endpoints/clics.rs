@@ -0,0 +1,189 @@
+use actix_web::{HttpRequest, HttpResponse, Responder, get, web};
+use autometrics::autometrics;
+use data::{ContestFile, RunsFile, TimeFile};
+use serde::Deserialize;
+use service::clics::export;
+use tracing::Level;
+
+use crate::app_data::{AppData, bearer_token};
+
+#[derive(Debug, Deserialize)]
+pub struct ClicsQuery {
+    contest: Option<String>,
+    /// Sede secret, when it is not sent as a bearer token.
+    /// With it, the unfrozen results of that sede are exported.
+    secret: Option<String>,
+}
+
+#[derive(Debug)]
+struct ClicsView {
+    contest_id: String,
+    contest: ContestFile,
+    runs: RunsFile,
+    time_file: TimeFile,
+}
+
+#[tracing::instrument(level = Level::DEBUG, skip(data, req))]
+async fn clics_view(
+    data: &AppData,
+    query: ClicsQuery,
+    req: &HttpRequest,
+) -> Result<ClicsView, HttpResponse> {
+    let contest_id = query.contest.unwrap_or_default();
+    let configs = data.config.load();
+    let Some((_, contest, secret)) = configs.get(&contest_id) else {
+        return Err(HttpResponse::NotFound().finish());
+    };
+
+    let key = bearer_token(req).or(query.secret.as_deref());
+    let sede = match key {
+        None => contest.titulo.clone(),
+        Some(key) => secret
+            .get_sede_by_secret(key)
+            .cloned()
+            .ok_or_else(|| HttpResponse::Forbidden().finish())?,
+    };
+
+    let db = data
+        .host(&contest_id)
+        .ok_or_else(|| HttpResponse::NotFound().finish())?
+        .view
+        .load();
+    if db.time_file < 0 {
+        return Err(HttpResponse::Forbidden().finish());
+    }
+
+    let runs = match key {
+        None => RunsFile::new(db.all_runs()).filter_sede(&sede),
+        Some(_) => db.run_file_secret.filter_sede(&sede),
+    };
+    let contest_file = contest.contest_file(db.contest_file_begin.clone(), &sede);
+
+    Ok(ClicsView {
+        contest_id,
+        contest: contest_file,
+        runs,
+        time_file: db.time_file,
+    })
+}
+
+#[get("/clics/scoreboard")]
+pub async fn get_scoreboard(
+    data: web::Data<AppData>,
+    query: web::Query<ClicsQuery>,
+    req: HttpRequest,
+) -> impl Responder {
+    get_scoreboard_fn(data, query.into_inner(), req).await
+}
+
+#[autometrics]
+async fn get_scoreboard_fn(
+    data: web::Data<AppData>,
+    query: ClicsQuery,
+    req: HttpRequest,
+) -> HttpResponse {
+    match clics_view(&data, query, &req).await {
+        Ok(view) => HttpResponse::Ok().json(export::scoreboard(
+            &view.contest,
+            &view.runs,
+            view.time_file,
+        )),
+        Err(response) => response,
+    }
+}
+
+#[get("/clics/contest")]
+pub async fn get_contest(
+    data: web::Data<AppData>,
+    query: web::Query<ClicsQuery>,
+    req: HttpRequest,
+) -> impl Responder {
+    match clics_view(&data, query.into_inner(), &req).await {
+        Ok(view) => HttpResponse::Ok().json(export::contest(&view.contest_id, &view.contest)),
+        Err(response) => response,
+    }
+}
+
+#[get("/clics/judgement-types")]
+pub async fn get_judgement_types() -> impl Responder {
+    HttpResponse::Ok().json(export::JUDGEMENT_TYPES)
+}
+
+#[get("/clics/problems")]
+pub async fn get_problems(
+    data: web::Data<AppData>,
+    query: web::Query<ClicsQuery>,
+    req: HttpRequest,
+) -> impl Responder {
+    match clics_view(&data, query.into_inner(), &req).await {
+        Ok(view) => HttpResponse::Ok().json(export::problems(&view.contest)),
+        Err(response) => response,
+    }
+}
+
+#[get("/clics/teams")]
+pub async fn get_teams(
+    data: web::Data<AppData>,
+    query: web::Query<ClicsQuery>,
+    req: HttpRequest,
+) -> impl Responder {
+    match clics_view(&data, query.into_inner(), &req).await {
+        Ok(view) => HttpResponse::Ok().json(export::teams(&view.contest)),
+        Err(response) => response,
+    }
+}
+
+#[get("/clics/submissions")]
+pub async fn get_submissions(
+    data: web::Data<AppData>,
+    query: web::Query<ClicsQuery>,
+    req: HttpRequest,
+) -> impl Responder {
+    match clics_view(&data, query.into_inner(), &req).await {
+        Ok(view) => HttpResponse::Ok().json(export::submissions(&view.runs)),
+        Err(response) => response,
+    }
+}
+
+#[get("/clics/judgements")]
+pub async fn get_judgements(
+    data: web::Data<AppData>,
+    query: web::Query<ClicsQuery>,
+    req: HttpRequest,
+) -> impl Responder {
+    match clics_view(&data, query.into_inner(), &req).await {
+        Ok(view) => HttpResponse::Ok().json(export::judgements(&view.runs)),
+        Err(response) => response,
+    }
+}
+
+#[get("/clics/event-feed")]
+pub async fn get_event_feed(
+    data: web::Data<AppData>,
+    query: web::Query<ClicsQuery>,
+    req: HttpRequest,
+) -> impl Responder {
+    get_event_feed_fn(data, query.into_inner(), req).await
+}
+
+#[autometrics]
+async fn get_event_feed_fn(
+    data: web::Data<AppData>,
+    query: ClicsQuery,
+    req: HttpRequest,
+) -> HttpResponse {
+    let view = match clics_view(&data, query, &req).await {
+        Ok(view) => view,
+        Err(response) => return response,
+    };
+
+    match export::event_feed(&view.contest_id, &view.contest, &view.runs) {
+        Ok(feed) => HttpResponse::Ok()
+            .content_type("application/x-ndjson")
+            .body(feed),
+        Err(err) => {
+            tracing::error!(?err, "failed rendering event feed");
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
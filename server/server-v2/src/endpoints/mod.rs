@@ -1 +1,6 @@
+pub mod admin_runs;
+pub mod balloons;
+pub mod clics;
+pub mod status;
+pub mod streams;
 pub mod update_contest;
@@ -0,0 +1,106 @@
+use std::collections::BTreeMap;
+
+use actix_web::{HttpResponse, Responder, get, web};
+use autometrics::autometrics;
+use serde::Serialize;
+use service::source::SourceHealth;
+use tracing::Level;
+
+use crate::app_data::AppData;
+
+#[derive(Debug, Serialize)]
+/// The health of the sources read by the server.
+struct Status {
+    /// The source of each contest, by contest.
+    /// Contests without a source of their own show the main source.
+    contests: BTreeMap<String, Option<SourceHealth>>,
+}
+
+#[derive(Debug, Serialize)]
+/// How fresh the data of a contest is.
+struct Freshness {
+    /// Seconds since the source last updated the contest, if it ever did.
+    age_secs: Option<u64>,
+    /// The source has been silent for too long.
+    stale: bool,
+}
+
+impl Freshness {
+    fn is_ready(&self) -> bool {
+        self.age_secs.is_some() && !self.stale
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct Health {
+    /// Every contest has data, and none is stale.
+    ready: bool,
+    contests: BTreeMap<String, Freshness>,
+}
+
+fn health(data: &AppData) -> Health {
+    let names = data.config.load().keys().cloned().collect::<Vec<_>>();
+
+    let mut contests = BTreeMap::new();
+    for name in names {
+        let Some(host) = data.host(&name) else {
+            continue;
+        };
+        let freshness = Freshness {
+            age_secs: host.view.age().map(|age| age.as_secs()),
+            stale: host.view.is_stale(),
+        };
+        contests.insert(name, freshness);
+    }
+
+    Health {
+        ready: contests.values().all(Freshness::is_ready),
+        contests,
+    }
+}
+
+/// The server is up. Tells how fresh the contests are, but never fails because of them.
+#[get("/health")]
+pub async fn get_health(data: web::Data<AppData>) -> impl Responder {
+    get_health_fn(data).await
+}
+
+#[tracing::instrument(level = Level::DEBUG, skip(data))]
+#[autometrics]
+async fn get_health_fn(data: web::Data<AppData>) -> HttpResponse {
+    HttpResponse::Ok().json(health(&data))
+}
+
+/// The server has fresh data for every contest, and can take the clients.
+#[get("/ready")]
+pub async fn get_ready(data: web::Data<AppData>) -> impl Responder {
+    get_ready_fn(data).await
+}
+
+#[tracing::instrument(level = Level::DEBUG, skip(data))]
+#[autometrics]
+async fn get_ready_fn(data: web::Data<AppData>) -> HttpResponse {
+    let health = health(&data);
+    if health.ready {
+        HttpResponse::Ok().json(health)
+    } else {
+        HttpResponse::ServiceUnavailable().json(health)
+    }
+}
+
+#[get("/status")]
+pub async fn get_status(data: web::Data<AppData>) -> impl Responder {
+    get_status_fn(data).await
+}
+
+#[tracing::instrument(level = Level::DEBUG, skip(data))]
+#[autometrics]
+async fn get_status_fn(data: web::Data<AppData>) -> HttpResponse {
+    HttpResponse::Ok().json(Status {
+        contests: data
+            .hosts
+            .iter()
+            .map(|(name, host)| (name.clone(), host.source_health()))
+            .collect(),
+    })
+}
@@ -0,0 +1,455 @@
+use std::time::Duration;
+
+use actix_web::{HttpRequest, HttpResponse, Responder, get, web};
+use autometrics::autometrics;
+use data::{RunTuple, configdata::Sede, remote_control::SequencedControl, sequenced::Sequenced};
+use serde::{Deserialize, Serialize};
+use service::membroadcast;
+use tokio::sync::broadcast::error::RecvError;
+use tracing::{Level, warn};
+
+use crate::{app_data::AppData, remote_control::follow_remote_control};
+
+/// Comments sent on idle streams, so proxies do not close them.
+const KEEPALIVE: Duration = Duration::from_secs(15);
+
+/// How long a poll waits for something new before answering with an empty list.
+const POLL_TIMEOUT: Duration = Duration::from_secs(25);
+
+/// Most runs answered by a single poll, the rest comes in the next one.
+const POLL_LIMIT: usize = 1000;
+
+#[derive(Debug, Deserialize)]
+pub struct StreamQuery {
+    contest: Option<String>,
+    /// Last sequence received, to resume the stream.
+    since: Option<u64>,
+}
+
+type Chunk = Result<web::Bytes, actix_web::Error>;
+
+fn keepalive() -> Chunk {
+    Ok(web::Bytes::from_static(b": keepalive\n\n"))
+}
+
+fn event(id: Option<u64>, value: &impl Serialize) -> Result<web::Bytes, serde_json::Error> {
+    let data = serde_json::to_string(value)?;
+    Ok(match id {
+        Some(id) => format!("id: {id}\ndata: {data}\n\n"),
+        None => format!("data: {data}\n\n"),
+    }
+    .into())
+}
+
+fn event_stream(stream: impl futures::Stream<Item = Chunk> + 'static) -> HttpResponse {
+    HttpResponse::Ok()
+        .content_type("text/event-stream")
+        .insert_header(("Cache-Control", "no-cache"))
+        // Asks nginx not to buffer the events.
+        .insert_header(("X-Accel-Buffering", "no"))
+        .streaming(stream)
+}
+
+fn sede_of(data: &AppData, sede_config: &str) -> Option<Sede> {
+    data.config
+        .load()
+        .get(sede_config)
+        .map(|(_config, contest, _secret)| contest.titulo.clone())
+}
+
+/// Browsers resend the id of the last event when an `EventSource` reconnects.
+fn last_event_id(req: &HttpRequest) -> Option<u64> {
+    req.headers()
+        .get("Last-Event-ID")
+        .and_then(|id| id.to_str().ok())
+        .and_then(|id| id.parse().ok())
+}
+
+#[get("/allruns_sse")]
+pub async fn get_allruns_sse(
+    data: web::Data<AppData>,
+    query: web::Query<StreamQuery>,
+    req: HttpRequest,
+) -> impl Responder {
+    get_allruns_sse_fn(data, query.into_inner(), req).await
+}
+
+#[autometrics]
+#[tracing::instrument(level = Level::DEBUG, skip(data, req))]
+async fn get_allruns_sse_fn(
+    data: web::Data<AppData>,
+    query: StreamQuery,
+    req: HttpRequest,
+) -> HttpResponse {
+    let sede_config = query.contest.unwrap_or_default();
+    let Some(sede) = sede_of(&data, &sede_config) else {
+        return HttpResponse::Forbidden().finish();
+    };
+
+    let permit = match data.connections.acquire(&req) {
+        Ok(permit) => permit,
+        Err(rejected) => return rejected,
+    };
+    let Some(host) = data.host(&sede_config) else {
+        return HttpResponse::NotFound().finish();
+    };
+    let since = last_event_id(&req).or(query.since);
+    let runs_rx = host.runs_tx.subscribe_since(since);
+
+    // The permit is released when the client goes away, and the stream is dropped.
+    event_stream(futures::stream::unfold(
+        (runs_rx, sede, permit),
+        |(mut runs_rx, sede, permit)| async move {
+            loop {
+                match tokio::time::timeout(KEEPALIVE, runs_rx.recv()).await {
+                    Err(_) => return Some((keepalive(), (runs_rx, sede, permit))),
+                    Ok(Ok(r)) => {
+                        if sede.team_belongs_str(&r.value.team_login) {
+                            match event(Some(r.seq), &r) {
+                                Ok(bytes) => return Some((Ok(bytes), (runs_rx, sede, permit))),
+                                Err(err) => warn!(?err, "failed serializing run"),
+                            }
+                        }
+                    }
+                    Ok(Err(err)) => {
+                        warn!(?err, "recv failed");
+                        return None;
+                    }
+                }
+            }
+        },
+    ))
+}
+
+#[get("/timer_sse")]
+pub async fn get_timer_sse(
+    data: web::Data<AppData>,
+    query: web::Query<StreamQuery>,
+    req: HttpRequest,
+) -> impl Responder {
+    get_timer_sse_fn(data, query.into_inner(), req).await
+}
+
+#[autometrics]
+#[tracing::instrument(level = Level::DEBUG, skip(data, req))]
+async fn get_timer_sse_fn(
+    data: web::Data<AppData>,
+    query: StreamQuery,
+    req: HttpRequest,
+) -> HttpResponse {
+    let Some(host) = data.host(query.contest.as_deref().unwrap_or_default()) else {
+        return HttpResponse::NotFound().finish();
+    };
+    let permit = match data.connections.acquire(&req) {
+        Ok(permit) => permit,
+        Err(rejected) => return rejected,
+    };
+    let time_rx = host.time_tx.subscribe();
+
+    event_stream(futures::stream::unfold(
+        (time_rx, None, permit),
+        |(mut time_rx, mut previous, permit)| async move {
+            loop {
+                match tokio::time::timeout(KEEPALIVE, time_rx.recv()).await {
+                    Err(_) => return Some((keepalive(), (time_rx, previous, permit))),
+                    Ok(Ok(time)) => {
+                        if previous.is_some_and(|x| x == time) {
+                            continue;
+                        }
+                        previous = Some(time);
+
+                        match event(None, &time) {
+                            Ok(bytes) => return Some((Ok(bytes), (time_rx, previous, permit))),
+                            Err(err) => warn!(?err, "failed serializing time"),
+                        }
+                    }
+                    // Only the last timer matters.
+                    Ok(Err(RecvError::Lagged(_))) => continue,
+                    Ok(Err(err)) => {
+                        warn!(?err, "recv failed");
+                        return None;
+                    }
+                }
+            }
+        },
+    ))
+}
+
+#[get("/allruns_poll")]
+pub async fn get_allruns_poll(
+    data: web::Data<AppData>,
+    query: web::Query<StreamQuery>,
+    req: HttpRequest,
+) -> impl Responder {
+    get_allruns_poll_fn(data, query.into_inner(), req).await
+}
+
+/// Answers with the runs after `since`, waiting for new ones when there are none.
+#[autometrics]
+#[tracing::instrument(level = Level::DEBUG, skip(data, req))]
+async fn get_allruns_poll_fn(
+    data: web::Data<AppData>,
+    query: StreamQuery,
+    req: HttpRequest,
+) -> HttpResponse {
+    let _permit = match data.connections.acquire(&req) {
+        Ok(permit) => permit,
+        Err(rejected) => return rejected,
+    };
+    let sede_config = query.contest.unwrap_or_default();
+    let Some(sede) = sede_of(&data, &sede_config) else {
+        return HttpResponse::Forbidden().finish();
+    };
+
+    let Some(host) = data.host(&sede_config) else {
+        return HttpResponse::NotFound().finish();
+    };
+    let mut runs_rx = host.runs_tx.subscribe_since(query.since);
+
+    HttpResponse::Ok().json(poll_runs(&mut runs_rx, &sede, POLL_LIMIT, POLL_TIMEOUT).await)
+}
+
+/// The runs of the sede ready in `runs_rx`, at most `limit` of them,
+/// waiting up to `timeout` for the first one.
+async fn poll_runs(
+    runs_rx: &mut membroadcast::Receiver<RunTuple>,
+    sede: &Sede,
+    limit: usize,
+    timeout: Duration,
+) -> Vec<Sequenced<RunTuple>> {
+    poll(
+        runs_rx,
+        |run| sede.team_belongs_str(&run.team_login),
+        limit,
+        timeout,
+    )
+    .await
+}
+
+/// The values ready in `rx` that are kept, at most `limit` of them,
+/// waiting up to `timeout` for the first one.
+async fn poll<T: Clone + membroadcast::MemoKey>(
+    rx: &mut membroadcast::Receiver<T>,
+    keep: impl Fn(&T) -> bool,
+    limit: usize,
+    timeout: Duration,
+) -> Vec<Sequenced<T>> {
+    let mut values = vec![];
+
+    let deadline = tokio::time::Instant::now() + timeout;
+    while values.len() < limit {
+        let next = match rx.try_recv() {
+            Ok(value) => value,
+            Err(_) if !values.is_empty() => break,
+            Err(_) => match tokio::time::timeout_at(deadline, rx.recv()).await {
+                Ok(Ok(value)) => value,
+                Ok(Err(err)) => {
+                    warn!(?err, "recv failed");
+                    break;
+                }
+                Err(_) => break,
+            },
+        };
+
+        if keep(&next.value) {
+            values.push(next);
+        }
+    }
+
+    values
+}
+
+#[get("/timer_poll")]
+pub async fn get_timer_poll(
+    data: web::Data<AppData>,
+    query: web::Query<StreamQuery>,
+    req: HttpRequest,
+) -> impl Responder {
+    get_timer_poll_fn(data, query.into_inner(), req).await
+}
+
+/// Answers with the next time of the timer, as a list like the runs,
+/// empty when the timer did not tick.
+#[autometrics]
+#[tracing::instrument(level = Level::DEBUG, skip(data, req))]
+async fn get_timer_poll_fn(
+    data: web::Data<AppData>,
+    query: StreamQuery,
+    req: HttpRequest,
+) -> HttpResponse {
+    let Some(host) = data.host(query.contest.as_deref().unwrap_or_default()) else {
+        return HttpResponse::NotFound().finish();
+    };
+    let _permit = match data.connections.acquire(&req) {
+        Ok(permit) => permit,
+        Err(rejected) => return rejected,
+    };
+    let mut time_rx = host.time_tx.subscribe();
+
+    let times = match tokio::time::timeout(POLL_TIMEOUT, time_rx.recv()).await {
+        Ok(Ok(time)) => vec![time],
+        Ok(Err(err)) => {
+            warn!(?err, "recv failed");
+            vec![]
+        }
+        Err(_) => vec![],
+    };
+
+    HttpResponse::Ok().json(times)
+}
+
+#[get("/remote_control_sse/{key}")]
+pub async fn get_remote_control_sse(
+    data: web::Data<AppData>,
+    key: web::Path<String>,
+    req: HttpRequest,
+) -> impl Responder {
+    get_remote_control_sse_fn(data, key.into_inner(), req).await
+}
+
+/// Follows a remote control, for screens that cannot open its websocket.
+/// Only the websocket sends commands, these only receive them.
+#[autometrics]
+#[tracing::instrument(level = Level::DEBUG, skip(data, req))]
+async fn get_remote_control_sse_fn(
+    data: web::Data<AppData>,
+    key: String,
+    req: HttpRequest,
+) -> HttpResponse {
+    let permit = match data.connections.acquire(&req) {
+        Ok(permit) => permit,
+        Err(rejected) => return rejected,
+    };
+    let control_rx = follow_remote_control(&data, key, None).await;
+
+    event_stream(futures::stream::unfold(
+        (control_rx, permit),
+        |(mut control_rx, permit)| async move {
+            loop {
+                match tokio::time::timeout(KEEPALIVE, control_rx.recv()).await {
+                    Err(_) => return Some((keepalive(), (control_rx, permit))),
+                    Ok(Ok(control)) => match event(None, control.value.message()) {
+                        Ok(bytes) => return Some((Ok(bytes), (control_rx, permit))),
+                        Err(err) => warn!(?err, "failed serializing control message"),
+                    },
+                    Ok(Err(_)) => return None,
+                }
+            }
+        },
+    ))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ControlQuery {
+    /// Last sequence received, so the messages sent between polls are not lost.
+    since: Option<u64>,
+}
+
+#[get("/remote_control_poll/{key}")]
+pub async fn get_remote_control_poll(
+    data: web::Data<AppData>,
+    key: web::Path<String>,
+    query: web::Query<ControlQuery>,
+    req: HttpRequest,
+) -> impl Responder {
+    get_remote_control_poll_fn(data, key.into_inner(), query.into_inner(), req).await
+}
+
+/// Answers with the messages of a remote control after `since`,
+/// waiting for new ones when there are none.
+#[autometrics]
+#[tracing::instrument(level = Level::DEBUG, skip(data, req))]
+async fn get_remote_control_poll_fn(
+    data: web::Data<AppData>,
+    key: String,
+    query: ControlQuery,
+    req: HttpRequest,
+) -> HttpResponse {
+    let _permit = match data.connections.acquire(&req) {
+        Ok(permit) => permit,
+        Err(rejected) => return rejected,
+    };
+    let mut control_rx = follow_remote_control(&data, key, query.since).await;
+
+    let messages = poll(&mut control_rx, |_| true, POLL_LIMIT, POLL_TIMEOUT)
+        .await
+        .into_iter()
+        .map(|control| SequencedControl {
+            seq: control.seq,
+            message: control.value.message().clone(),
+        })
+        .collect::<Vec<_>>();
+
+    HttpResponse::Ok().json(messages)
+}
+
+#[cfg(test)]
+mod tests {
+    use data::{Answer, configdata::SedeEntry, test_util::run};
+
+    use super::*;
+
+    fn sede(codes: &str) -> Sede {
+        let entry: SedeEntry =
+            toml::from_str(&format!("name = \"sede\"\ncodes = [\"{codes}\"]")).unwrap();
+        entry.into_sede()
+    }
+
+    const NO_WAIT: Duration = Duration::from_millis(10);
+
+    #[tokio::test]
+    async fn test_poll_filters_sede() {
+        let (runs_tx, _) = membroadcast::channel(100);
+        runs_tx.send_memo(run(1, "teambrsp1", 10, Answer::No { run_id: 1 }));
+        runs_tx.send_memo(run(2, "teambrce1", 10, Answer::No { run_id: 2 }));
+        runs_tx.send_memo(run(3, "teambrsp2", 10, Answer::No { run_id: 3 }));
+
+        let runs = poll_runs(&mut runs_tx.subscribe(), &sede("^teambrsp"), 10, NO_WAIT).await;
+        assert_eq!(
+            runs.iter().map(|r| r.value.id).collect::<Vec<_>>(),
+            vec![1, 3]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_poll_limit_and_since() {
+        let (runs_tx, _) = membroadcast::channel(100);
+        for id in 0..5 {
+            runs_tx.send_memo(run(id, "team1", 10, Answer::No { run_id: id }));
+        }
+        let everyone = sede("");
+
+        let first = poll_runs(&mut runs_tx.subscribe(), &everyone, 3, NO_WAIT).await;
+        assert_eq!(
+            first.iter().map(|r| r.value.id).collect::<Vec<_>>(),
+            vec![0, 1, 2]
+        );
+
+        // The next poll resumes after the last run received.
+        let since = first.last().map(|r| r.seq);
+        let rest = poll_runs(&mut runs_tx.subscribe_since(since), &everyone, 3, NO_WAIT).await;
+        assert_eq!(
+            rest.iter().map(|r| r.value.id).collect::<Vec<_>>(),
+            vec![3, 4]
+        );
+
+        let since = rest.last().map(|r| r.seq);
+        let none = poll_runs(&mut runs_tx.subscribe_since(since), &everyone, 3, NO_WAIT).await;
+        assert!(none.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_poll_waits_for_new_runs() {
+        let (runs_tx, _) = membroadcast::channel(100);
+        let mut runs_rx = runs_tx.subscribe();
+
+        let sender = runs_tx.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            sender.send_memo(run(1, "team1", 10, Answer::No { run_id: 1 }));
+        });
+
+        let runs = poll_runs(&mut runs_rx, &sede(""), 10, Duration::from_secs(5)).await;
+        assert_eq!(runs.len(), 1);
+    }
+}
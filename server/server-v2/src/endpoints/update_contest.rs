@@ -1,37 +1,112 @@
-use actix_web::{HttpRequest, HttpResponse, Responder, put, web};
-use data::contest_state::ContestState;
-use service::dbupdate_v2::update_runs_from_data;
+use actix_web::{HttpRequest, HttpResponse, Responder, patch, put, web};
+use data::{
+    configdata::Role,
+    contest_state::{ContestDelta, ContestState, UpdateAck},
+};
+use serde::Deserialize;
+use service::{
+    dbupdate_v2::{update_runs_from_data, update_runs_from_delta},
+    errors::Error,
+};
 
-use crate::app_data::AppData;
+use crate::{app_data::AppData, contest_host::ContestHost};
 
-const API_KEY: &str = "apikey";
+#[derive(Debug, Deserialize)]
+pub struct UpdateQuery {
+    contest: Option<String>,
+    /// Sequence of a full update, the base of the delta updates that follow.
+    sequence: Option<u64>,
+}
+
+/// The host of the contest to update, when it is configured, and not read from a source.
+fn uploaded_host<'a>(
+    data: &'a AppData,
+    query: &UpdateQuery,
+) -> Result<&'a ContestHost, HttpResponse> {
+    let name = query.contest.as_deref().unwrap_or_default();
+    let Some(host) = data.host(name) else {
+        return Err(HttpResponse::NotFound().finish());
+    };
+    if host.source.is_some() {
+        tracing::warn!(
+            ?name,
+            "rejected upload, the contest is read from its source"
+        );
+        return Err(HttpResponse::Conflict()
+            .body("the contest is read from its own source, it cannot be uploaded"));
+    }
+    Ok(host)
+}
 
 #[put("/contests")]
 pub async fn update_contest(
     data: web::Data<AppData>,
     create_runs: web::Json<ContestState>,
+    query: web::Query<UpdateQuery>,
     req: HttpRequest,
 ) -> impl Responder {
-    let contest_key = match &data.server_api_key {
-        Some(key) => key,
-        None => return HttpResponse::Unauthorized().finish(),
+    if !data.authorize(&req, Role::Uploader) {
+        return HttpResponse::Unauthorized().finish();
+    }
+
+    let contest_state = create_runs.into_inner();
+    let host = match uploaded_host(&data, &query) {
+        Ok(host) => host,
+        Err(rejected) => return rejected,
     };
 
-    if req
-        .headers()
-        .get(API_KEY)
-        .is_none_or(|k| k.as_bytes() != contest_key.as_bytes())
+    match update_runs_from_data(
+        contest_state,
+        query.sequence,
+        &host.shared_db,
+        &host.runs_tx,
+        &host.time_tx,
+        host.snapshot_store.as_ref(),
+    )
+    .await
     {
+        Ok(ack) => HttpResponse::Created().json(ack),
+        Err(e) => {
+            tracing::error!(?e, "failed updating runs from data");
+
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
+
+#[patch("/contests")]
+pub async fn update_contest_delta(
+    data: web::Data<AppData>,
+    delta: web::Json<ContestDelta>,
+    query: web::Query<UpdateQuery>,
+    req: HttpRequest,
+) -> impl Responder {
+    if !data.authorize(&req, Role::Uploader) {
         return HttpResponse::Unauthorized().finish();
-    };
+    }
 
-    let contest_state = create_runs.into_inner();
+    let host = match uploaded_host(&data, &query) {
+        Ok(host) => host,
+        Err(rejected) => return rejected,
+    };
 
-    match update_runs_from_data(contest_state, &data.shared_db, &data.runs_tx, &data.time_tx).await
+    match update_runs_from_delta(
+        delta.into_inner(),
+        &host.shared_db,
+        &host.runs_tx,
+        &host.time_tx,
+        host.snapshot_store.as_ref(),
+    )
+    .await
     {
-        Ok(()) => HttpResponse::Created().finish(),
+        Ok(ack) => HttpResponse::Ok().json(ack),
+        // The uploader must send a full update.
+        Err(Error::SequenceMismatch { expected, got }) => {
+            tracing::warn!(?expected, got, "delta update out of sequence");
+            HttpResponse::Conflict().json(UpdateAck { sequence: expected })
+        }
         Err(e) => {
-            tracing::error!(?e, "failed updating runs from data");
+            tracing::error!(?e, "failed updating runs from delta");
 
             HttpResponse::InternalServerError().finish()
         }
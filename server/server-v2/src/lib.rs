@@ -1,8 +1,13 @@
 mod api;
 mod app_data;
+mod config_reload;
+mod connections;
+mod contest_cache;
+mod contest_host;
 mod endpoints;
 pub mod metrics;
 mod remote_control;
+mod revelation_session;
 mod volumes;
 
 use std::{collections::HashMap, sync::Arc};
@@ -10,13 +15,13 @@ use std::{collections::HashMap, sync::Arc};
 use actix_cors::Cors;
 use actix_web::*;
 use app_data::AppData;
-use tokio::sync::broadcast;
-
+use config_reload::{SharedConfig, get_config_ws, reload_config};
+use connections::ConnectionLimiter;
+use contest_host::ContestHost;
 use metrics::get_metrics;
 use remote_control::remote_control_ws;
-use service::DB;
-use service::dbupdate_v2::db_update_loop;
-use service::membroadcast;
+use revelation_session::{RevelationSessions, revelation_ws};
+use service::source::Source;
 use service::{app_config::AppConfig, errors::ServiceResult, http::HttpConfig};
 use tokio::sync::Mutex;
 use tracing_actix_web::TracingLogger;
@@ -25,46 +30,80 @@ use volumes::configure_volumes;
 pub async fn serve_config(
     AppConfig {
         config,
-        boca_url,
-        server_config: HttpConfig { port },
+        config_loader,
+        source,
+        server_config: HttpConfig {
+            port,
+            connection_limits,
+        },
         volumes,
-        server_api_key,
+        snapshot_store,
+        stale_after,
     }: AppConfig,
 ) -> ServiceResult<()> {
-    let config = Arc::new(config);
-
-    let shared_db = Arc::new(Mutex::new(DB::empty()));
-    let (runs_tx, _) = membroadcast::channel(1000000);
-    let (time_tx, _) = broadcast::channel(1000000);
+    let config = Arc::new(SharedConfig::new(config));
 
-    let remote_control = Arc::new(Mutex::new(HashMap::new()));
+    let main_host = match source {
+        Some(source) => {
+            Some(ContestHost::start(Some(source), snapshot_store.clone(), stale_after).await?)
+        }
+        None => None,
+    };
 
-    if let Some(url) = boca_url {
-        let _update = tokio::task::spawn(db_update_loop(
-            url.clone(),
-            shared_db.clone(),
-            runs_tx.clone(),
-            time_tx.clone(),
-        ));
+    // Without a main source, the contests without a source of their own share one host,
+    // updated by the uploader, as they share the main host otherwise.
+    let mut uploaded_host: Option<ContestHost> = None;
+    let mut hosts = HashMap::new();
+    for (name, (config_contest, _, _)) in config.load().iter() {
+        let host = match (Source::from_config(config_contest), &main_host) {
+            (Some(source), _) => {
+                tracing::info!(?name, "contest with its own source");
+                let store = snapshot_store.as_ref().map(|store| store.contest(name));
+                ContestHost::start(Some(source), store, stale_after).await?
+            }
+            (None, Some(main_host)) => main_host.clone(),
+            (None, None) => match &uploaded_host {
+                Some(host) => host.clone(),
+                None => {
+                    let host =
+                        ContestHost::start(None, snapshot_store.clone(), stale_after).await?;
+                    uploaded_host.insert(host).clone()
+                }
+            },
+        };
+        hosts.insert(name.clone(), host);
     }
+    let hosts = Arc::new(hosts);
+
+    let remote_control = Arc::new(Mutex::new(HashMap::new()));
+    let revelation_sessions = RevelationSessions::default();
+    let connections = ConnectionLimiter::new(connection_limits);
+    let delivered_balloons = Arc::new(Mutex::new(
+        endpoints::balloons::load_delivered(snapshot_store.as_ref()).await?,
+    ));
 
     HttpServer::new(move || {
         App::new()
             .wrap(TracingLogger::default())
             .wrap(Cors::permissive())
             .app_data(web::Data::new(AppData {
-                shared_db: shared_db.clone(),
-                runs_tx: runs_tx.clone(),
-                time_tx: time_tx.clone(),
+                hosts: hosts.clone(),
                 config: config.clone(),
+                config_loader: config_loader.clone(),
                 remote_control: remote_control.clone(),
-                server_api_key: server_api_key.clone(),
+                revelation_sessions: revelation_sessions.clone(),
+                delivered_balloons: delivered_balloons.clone(),
+                snapshot_store: snapshot_store.clone(),
+                connections: connections.clone(),
             }))
             .service(
                 web::scope("api")
                     .configure(api::configure)
                     .service(get_metrics)
-                    .service(remote_control_ws),
+                    .service(remote_control_ws)
+                    .service(revelation_ws)
+                    .service(reload_config)
+                    .service(get_config_ws),
             )
             .service(configure_volumes(volumes.clone()))
     })
@@ -1,18 +1,20 @@
 use std::time::Duration;
 
-use actix_web::{get, web, HttpRequest, HttpResponse};
+use actix_web::{HttpRequest, HttpResponse, get, web};
 use actix_ws::{Message, MessageStream, Session};
 use autometrics::autometrics;
-use data::remote_control::ControlMessage;
+use data::{configdata::Role, remote_control::ControlMessage, sequenced::Sequenced};
 use futures::StreamExt;
-use tokio::sync::broadcast::{
-    error::{RecvError, SendError},
-    Receiver, Sender,
-};
-use tokio_stream::wrappers::BroadcastStream;
-use tracing::{debug, instrument, Level};
+use service::membroadcast::{self, MemoKey, Receiver, Sender};
+use tracing::{Level, debug, instrument};
 
-use crate::app_data::AppData;
+use crate::app_data::{AppData, bearer_token};
+
+#[derive(Debug, serde::Deserialize)]
+struct TokenQuery {
+    /// The operator token, for browsers, that cannot send headers in websockets.
+    token: Option<String>,
+}
 
 #[get("/remote_control/{key}")]
 async fn remote_control_ws(
@@ -20,8 +22,9 @@ async fn remote_control_ws(
     req: HttpRequest,
     body: web::Payload,
     key: web::Path<String>,
+    query: web::Query<TokenQuery>,
 ) -> Result<HttpResponse, actix_web::Error> {
-    run_remote_control_ws(data, req, body, key.into_inner()).await
+    run_remote_control_ws(data, req, body, key.into_inner(), query.into_inner()).await
 }
 
 pub type ControlSender = Sender<ConnectionControlMessage>;
@@ -32,17 +35,48 @@ pub struct ConnectionControlMessage {
     message: ControlMessage,
 }
 
+impl ConnectionControlMessage {
+    pub fn message(&self) -> &ControlMessage {
+        &self.message
+    }
+}
+
+/// Only the last message of each kind is replayed, the screens follow from it.
+impl MemoKey for ConnectionControlMessage {
+    fn memo_key(&self) -> i64 {
+        match self.message {
+            ControlMessage::WindowScroll(_) => 0,
+            ControlMessage::QueryString(_) => 1,
+            ControlMessage::PhotoState(_) => 2,
+        }
+    }
+}
+
 fn create_remote_control() -> ControlSender {
-    let (sender, _) = tokio::sync::broadcast::channel(100);
+    let (sender, _) = membroadcast::channel(100);
     sender
 }
 
+async fn remote_control(data: &AppData, key: String) -> ControlSender {
+    let mut lock = data.remote_control.lock().await;
+
+    lock.entry(key)
+        .or_insert_with(create_remote_control)
+        .clone()
+}
+
+/// Follows the messages sent to the remote control `key` after `since`, without sending any.
+/// Without `since`, starts from the last message of each kind.
+pub async fn follow_remote_control(
+    data: &AppData,
+    key: String,
+    since: Option<u64>,
+) -> Receiver<ConnectionControlMessage> {
+    remote_control(data, key).await.subscribe_since(since)
+}
+
 #[derive(Debug, thiserror::Error)]
 enum Error {
-    #[error(transparent)]
-    RecvError(#[from] RecvError),
-    #[error(transparent)]
-    SendError(#[from] SendError<ConnectionControlMessage>),
     #[error(transparent)]
     Serde(#[from] serde_json::Error),
     #[error(transparent)]
@@ -53,16 +87,17 @@ enum Error {
 
 #[instrument(skip(rec, session), err)]
 async fn send_to_clients(
-    rec: Receiver<ConnectionControlMessage>,
+    mut rec: Receiver<ConnectionControlMessage>,
     mut session: Session,
     connection_request_id: u64,
 ) -> Result<(), Error> {
-    let mut rec_stream = BroadcastStream::new(rec);
-
-    while let Some(Ok(ConnectionControlMessage {
-        request_id,
-        message,
-    })) = rec_stream.next().await
+    while let Ok(Sequenced {
+        value: ConnectionControlMessage {
+            request_id,
+            message,
+        },
+        ..
+    }) = rec.recv().await
     {
         if request_id != connection_request_id {
             let text = serde_json::to_string(&message)?;
@@ -90,14 +125,19 @@ async fn read_from_clients(
     stream: &mut MessageStream,
     sender: Sender<ConnectionControlMessage>,
     request_id: u64,
+    is_operator: bool,
 ) -> Result<(), Error> {
     while let Some(Ok(raw_message)) = stream.next().await {
         if let Some(message) = get_text(raw_message)? {
+            if !is_operator {
+                debug!(?message, "ignored, not an operator");
+                continue;
+            }
             debug!(?message, "receive");
-            sender.send(ConnectionControlMessage {
+            sender.send_memo(ConnectionControlMessage {
                 request_id,
                 message,
-            })?;
+            });
         } else {
             tokio::time::sleep(Duration::from_secs(1)).await
         }
@@ -106,26 +146,36 @@ async fn read_from_clients(
     Ok(())
 }
 
+/// Once operator tokens are configured, only operators send commands, the others only follow.
+fn is_operator(data: &AppData, req: &HttpRequest, query: &TokenQuery) -> bool {
+    !data.defines_role(Role::RemoteControl)
+        || bearer_token(req)
+            .or(query.token.as_deref())
+            .is_some_and(|token| data.has_role(token, Role::RemoteControl))
+}
+
 #[autometrics]
-#[tracing::instrument(level = Level::DEBUG, skip(data, body), ret)]
+#[tracing::instrument(level = Level::DEBUG, skip(data, body, query), ret)]
 async fn run_remote_control_ws(
     data: web::Data<AppData>,
     req: HttpRequest,
     body: web::Payload,
     key: String,
+    query: TokenQuery,
 ) -> Result<HttpResponse, actix_web::Error> {
+    let is_operator = is_operator(&data, &req, &query);
+    let permit = match data.connections.acquire(&req) {
+        Ok(permit) => permit,
+        Err(rejected) => return Ok(rejected),
+    };
     let (response, session, mut msg_stream) = actix_ws::handle(&req, body)?;
 
-    let sender = {
-        let mut lock = data.remote_control.lock().await;
+    let sender = remote_control(&data, key).await;
 
-        lock.entry(key).or_insert(create_remote_control()).clone()
-    };
-
-    let rec = sender.subscribe();
+    let rec = sender.subscribe_new();
 
     let request_id = rand::random();
-    tracing::info!(?request_id, "established remote control");
+    tracing::info!(?request_id, is_operator, "established remote control");
 
     actix_web::rt::spawn(async move {
         if let Err(err) = send_to_clients(rec, session, request_id).await {
@@ -133,11 +183,56 @@ async fn run_remote_control_ws(
         }
     });
 
+    // The permit is released when the client stops sending, and goes away.
     actix_web::rt::spawn(async move {
-        if let Err(err) = read_from_clients(&mut msg_stream, sender, request_id).await {
+        let _permit = permit;
+        if let Err(err) = read_from_clients(&mut msg_stream, sender, request_id, is_operator).await
+        {
             tracing::debug!(?err, "failed reading");
         }
     });
 
     Ok(response)
 }
+
+#[cfg(test)]
+mod tests {
+    use data::remote_control::{QueryString, WindowScroll};
+
+    use super::*;
+
+    fn control(message: ControlMessage) -> ConnectionControlMessage {
+        ConnectionControlMessage {
+            request_id: 0,
+            message,
+        }
+    }
+
+    fn scroll(y: f64) -> ControlMessage {
+        ControlMessage::WindowScroll(WindowScroll { y })
+    }
+
+    #[tokio::test]
+    async fn test_follow_since() {
+        let sender = create_remote_control();
+        sender.send_memo(control(scroll(1.0)));
+        sender.send_memo(control(ControlMessage::QueryString(QueryString {
+            query: "q".to_string(),
+        })));
+        sender.send_memo(control(scroll(2.0)));
+
+        let mut rx = sender.subscribe_since(None);
+        let query = rx.recv().await.unwrap();
+        assert!(matches!(
+            query.value.message,
+            ControlMessage::QueryString(_)
+        ));
+        assert_eq!(rx.recv().await.unwrap().value.message, scroll(2.0));
+        assert!(rx.try_recv().is_err());
+
+        let mut resumed = sender.subscribe_since(Some(query.seq));
+        sender.send_memo(control(scroll(3.0)));
+        assert_eq!(resumed.recv().await.unwrap().value.message, scroll(2.0));
+        assert_eq!(resumed.recv().await.unwrap().value.message, scroll(3.0));
+    }
+}
@@ -0,0 +1,399 @@
+use std::{
+    collections::HashMap,
+    sync::{
+        Arc,
+        atomic::{AtomicUsize, Ordering},
+    },
+    time::Duration,
+};
+
+use actix_web::{HttpRequest, HttpResponse, get, web};
+use actix_ws::{Message, MessageStream, Session};
+use autometrics::autometrics;
+use data::{
+    configdata::{Contest, Sede},
+    revelation::{RevelationCommand, RevelationState, RevelationUpdate, SentTeams},
+};
+use futures::StreamExt;
+use serde::Deserialize;
+use service::PublishedView;
+use tokio::sync::{
+    Mutex,
+    broadcast::{self, Sender, error::RecvError},
+};
+use tracing::{Level, debug, instrument, warn};
+
+use crate::app_data::{AppData, bearer_token};
+
+/// Sessions of one sede, each with its own name, kept at the same time.
+const MAX_SESSIONS_PER_SEDE: usize = 4;
+
+/// How long a session outlives its last viewer, so the revealer can reload the page.
+const SESSION_GRACE: Duration = Duration::from_secs(300);
+
+pub type RevelationSessions = Arc<Mutex<HashMap<String, Arc<RevelationSession>>>>;
+
+/// Where a revelation session reads its contest from.
+struct Origin {
+    view: Arc<PublishedView>,
+    sede: Sede,
+    contest: Contest,
+}
+
+impl Origin {
+    /// A ceremony from the current data of the contest, if it has started.
+    fn state(&self) -> Option<RevelationState> {
+        let db = self.view.load();
+        if db.time_file < 0 {
+            return None;
+        }
+
+        let contest = self
+            .contest
+            .contest_file(db.contest_file_begin.clone(), &self.sede);
+        let runs = db.run_file_secret.filter_sede(&self.sede);
+
+        Some(RevelationState::new(contest, runs, &self.sede))
+    }
+}
+
+/// A revelation ceremony hosted by the server.
+/// Every viewer of the session renders the same state.
+pub struct RevelationSession {
+    state: Mutex<Ceremony>,
+    /// Each step, with only the teams that changed.
+    views: Sender<Arc<String>>,
+    origin: Origin,
+    /// Changed only while holding the sessions, so a session is never dropped as a viewer joins.
+    viewers: AtomicUsize,
+}
+
+impl RevelationSession {
+    fn new(origin: Origin) -> Option<Self> {
+        let (views, _) = broadcast::channel(100);
+        Some(Self {
+            state: Mutex::new(Ceremony::new(origin.state()?)),
+            views,
+            origin,
+            viewers: AtomicUsize::new(0),
+        })
+    }
+
+    /// The whole view, for viewers that just connected, or fell behind.
+    async fn current_view(&self) -> Result<String, Error> {
+        let ceremony = self.state.lock().await;
+        Ok(serde_json::to_string(&RevelationUpdate::Full(
+            ceremony.state.view(),
+        ))?)
+    }
+
+    async fn apply(&self, command: RevelationCommand) -> Result<(), Error> {
+        let text = {
+            let mut ceremony = self.state.lock().await;
+            let Ceremony { state, sent } = &mut *ceremony;
+            // Resetting starts over from the current data, with the runs judged since the session began.
+            let fresh = match command {
+                RevelationCommand::Reset => self.origin.state(),
+                _ => None,
+            };
+            if let Some(fresh) = fresh {
+                debug!("restarted revelation from the current data");
+                *state = fresh;
+            } else if let Err(err) = state.apply(command) {
+                warn!(?err, ?command, "failed applying revelation command");
+            }
+            serde_json::to_string(&RevelationUpdate::Delta(state.delta(sent)))?
+        };
+
+        // No viewers is not an error, the next one gets the current view.
+        let _ = self.views.send(Arc::new(text));
+        Ok(())
+    }
+}
+
+/// The state of a ceremony, with the teams as its viewers last got them.
+struct Ceremony {
+    state: RevelationState,
+    sent: SentTeams,
+}
+
+impl Ceremony {
+    fn new(state: RevelationState) -> Self {
+        let mut sent = SentTeams::default();
+        state.delta(&mut sent);
+        Self { state, sent }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct RevelationQuery {
+    contest: Option<String>,
+    /// The secret of the revealer, when it is not sent as a bearer token.
+    /// Browsers cannot set headers on websockets, so the Reveleitor sends it here.
+    secret: Option<String>,
+}
+
+#[derive(Debug, thiserror::Error)]
+enum Error {
+    #[error(transparent)]
+    Serde(#[from] serde_json::Error),
+    #[error(transparent)]
+    Closed(#[from] actix_ws::Closed),
+}
+
+#[get("/revelation/{name}")]
+async fn revelation_ws(
+    data: web::Data<AppData>,
+    req: HttpRequest,
+    body: web::Payload,
+    name: web::Path<String>,
+    query: web::Query<RevelationQuery>,
+) -> Result<HttpResponse, actix_web::Error> {
+    run_revelation_ws(data, req, body, name.into_inner(), query.into_inner()).await
+}
+
+#[instrument(skip(session, revelation), err)]
+async fn send_to_viewers(
+    revelation: Arc<RevelationSession>,
+    mut session: Session,
+) -> Result<(), Error> {
+    let mut views = revelation.views.subscribe();
+    session.text(revelation.current_view().await?).await?;
+
+    loop {
+        match views.recv().await {
+            Ok(text) => session.text(text.as_str()).await?,
+            Err(RecvError::Lagged(skipped)) => {
+                debug!(skipped, "viewer lagged, resending current view");
+                session.text(revelation.current_view().await?).await?
+            }
+            Err(RecvError::Closed) => break,
+        }
+    }
+
+    Ok(session.close(None).await?)
+}
+
+#[instrument(skip(stream, revelation), err)]
+async fn read_commands(
+    stream: &mut MessageStream,
+    revelation: Arc<RevelationSession>,
+) -> Result<(), Error> {
+    while let Some(Ok(message)) = stream.next().await {
+        match message {
+            Message::Text(text) => {
+                let command = match serde_json::from_str(&text) {
+                    Ok(command) => command,
+                    Err(err) => {
+                        warn!(?err, ?text, "invalid revelation command");
+                        continue;
+                    }
+                };
+                debug!(?command, "receive");
+                revelation.apply(command).await?;
+            }
+            Message::Close(_) => break,
+            _ => (),
+        }
+    }
+
+    Ok(())
+}
+
+#[autometrics]
+#[tracing::instrument(level = Level::DEBUG, skip(data, body), ret)]
+async fn run_revelation_ws(
+    data: web::Data<AppData>,
+    req: HttpRequest,
+    body: web::Payload,
+    name: String,
+    query: RevelationQuery,
+) -> Result<HttpResponse, actix_web::Error> {
+    let contest_name = query.contest.unwrap_or_default();
+    let configs = data.config.load();
+    let Some((contest, sede)) = configs
+        .get(&contest_name)
+        .zip(bearer_token(&req).or(query.secret.as_deref()))
+        .and_then(|((_, contest, secret), key)| {
+            secret
+                .get_sede_by_secret(key)
+                .map(|sede| (contest.clone(), sede.clone()))
+        })
+    else {
+        return Ok(HttpResponse::Forbidden().finish());
+    };
+
+    let Some(host) = data.host(&contest_name) else {
+        return Ok(HttpResponse::NotFound().finish());
+    };
+    let permit = match data.connections.acquire(&req) {
+        Ok(permit) => permit,
+        Err(rejected) => return Ok(rejected),
+    };
+
+    let sede_key = format!("{contest_name}/{}", sede.entry.name);
+    let origin = Origin {
+        view: host.view.clone(),
+        sede,
+        contest,
+    };
+    let (key, revelation) = match join(&data.revelation_sessions, &sede_key, &name, origin).await {
+        Ok(joined) => joined,
+        Err(rejected) => return Ok(rejected),
+    };
+
+    let (response, session, mut msg_stream) = match actix_ws::handle(&req, body) {
+        Ok(handled) => handled,
+        Err(err) => {
+            let sessions = data.revelation_sessions.clone();
+            actix_web::rt::spawn(async move {
+                leave(&sessions, &key, &revelation, SESSION_GRACE).await;
+            });
+            return Err(err);
+        }
+    };
+
+    // Sending stops with the reading, when the viewer goes away, releasing the permit and the session.
+    let sessions = data.revelation_sessions.clone();
+    actix_web::rt::spawn(async move {
+        let _permit = permit;
+        tokio::select! {
+            result = send_to_viewers(revelation.clone(), session) => {
+                if let Err(err) = result {
+                    tracing::debug!(?err, "failed sending");
+                }
+            }
+            result = read_commands(&mut msg_stream, revelation.clone()) => {
+                if let Err(err) = result {
+                    tracing::debug!(?err, "failed reading");
+                }
+            }
+        }
+        leave(&sessions, &key, &revelation, SESSION_GRACE).await;
+    });
+
+    Ok(response)
+}
+
+/// Joins the session `name` of the sede, creating it if there is none.
+/// Returns the key of the session, or the response refusing it.
+async fn join(
+    sessions: &RevelationSessions,
+    sede_key: &str,
+    name: &str,
+    origin: Origin,
+) -> Result<(String, Arc<RevelationSession>), HttpResponse> {
+    let mut sessions = sessions.lock().await;
+    let key = format!("{sede_key}/{name}");
+
+    let revelation = match sessions.get(&key) {
+        Some(revelation) => revelation.clone(),
+        None => {
+            let prefix = format!("{sede_key}/");
+            let open = sessions.keys().filter(|k| k.starts_with(&prefix)).count();
+            if open >= MAX_SESSIONS_PER_SEDE {
+                warn!(?key, open, "too many revelation sessions");
+                return Err(HttpResponse::TooManyRequests().finish());
+            }
+
+            let Some(revelation) = RevelationSession::new(origin) else {
+                return Err(HttpResponse::Forbidden().finish());
+            };
+
+            tracing::info!(?key, "created revelation session");
+            let revelation = Arc::new(revelation);
+            sessions.insert(key.clone(), revelation.clone());
+            revelation
+        }
+    };
+
+    revelation.viewers.fetch_add(1, Ordering::SeqCst);
+    Ok((key, revelation))
+}
+
+/// Leaves the session, and drops it if nobody joins it again within `grace`.
+async fn leave(
+    sessions: &RevelationSessions,
+    key: &str,
+    revelation: &Arc<RevelationSession>,
+    grace: Duration,
+) {
+    {
+        let _sessions = sessions.lock().await;
+        if revelation.viewers.fetch_sub(1, Ordering::SeqCst) > 1 {
+            return;
+        }
+    }
+
+    tokio::time::sleep(grace).await;
+
+    let mut sessions = sessions.lock().await;
+    if revelation.viewers.load(Ordering::SeqCst) == 0
+        && sessions
+            .get(key)
+            .is_some_and(|session| Arc::ptr_eq(session, revelation))
+    {
+        sessions.remove(key);
+        tracing::info!(?key, "dropped revelation session without viewers");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use data::configdata::SedeEntry;
+    use service::DB;
+
+    use super::*;
+
+    fn origin(db: &DB) -> Origin {
+        Origin {
+            view: db.view(),
+            sede: SedeEntry::default().into_sede(),
+            contest: Contest {
+                sedes: HashMap::new(),
+                titulo: SedeEntry::default().into_sede(),
+                scoring: None,
+            },
+        }
+    }
+
+    #[tokio::test]
+    async fn test_sessions_per_sede() {
+        let db = DB::empty();
+        let sessions = RevelationSessions::default();
+
+        for name in 0..MAX_SESSIONS_PER_SEDE {
+            join(&sessions, "c/sede", &name.to_string(), origin(&db))
+                .await
+                .unwrap();
+        }
+        let rejected = join(&sessions, "c/sede", "another", origin(&db))
+            .await
+            .err()
+            .unwrap();
+        assert_eq!(
+            rejected.status(),
+            actix_web::http::StatusCode::TOO_MANY_REQUESTS
+        );
+
+        // Joining a session again, or one of another sede, is not limited.
+        join(&sessions, "c/sede", "0", origin(&db)).await.unwrap();
+        join(&sessions, "c/other", "0", origin(&db)).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_session_dropped_after_last_viewer() {
+        let db = DB::empty();
+        let sessions = RevelationSessions::default();
+
+        let (key, first) = join(&sessions, "c/sede", "s", origin(&db)).await.unwrap();
+        let (_, second) = join(&sessions, "c/sede", "s", origin(&db)).await.unwrap();
+        assert!(Arc::ptr_eq(&first, &second));
+
+        leave(&sessions, &key, &first, Duration::ZERO).await;
+        assert!(sessions.lock().await.contains_key(&key));
+
+        leave(&sessions, &key, &second, Duration::ZERO).await;
+        assert!(sessions.lock().await.is_empty());
+    }
+}
@@ -1,13 +1,25 @@
-use std::collections::HashMap;
+use std::{collections::HashMap, sync::Arc, time::Duration};
 
 use data::configdata::{ConfigContest, Contest, Secret};
 
-use crate::{http::HttpConfig, volume::Volume};
+use crate::{
+    errors::ServiceResult, http::HttpConfig, snapshot::SnapshotStore, source::Source,
+    volume::Volume,
+};
+
+/// Contest configs by the `?contest=` key.
+pub type ConfigMap = HashMap<String, (ConfigContest, Contest, Secret)>;
+
+/// Reads the config files again, so they can be reloaded while the server runs.
+pub type ConfigLoader = Arc<dyn Fn() -> ServiceResult<ConfigMap> + Send + Sync>;
 
 pub struct AppConfig {
-    pub config: HashMap<String, (ConfigContest, Contest, Secret)>,
-    pub boca_url: Option<String>,
+    pub config: ConfigMap,
+    pub config_loader: Option<ConfigLoader>,
+    pub source: Option<Source>,
     pub server_config: HttpConfig,
     pub volumes: Vec<Volume>,
-    pub server_api_key: Option<String>,
+    pub snapshot_store: Option<SnapshotStore>,
+    /// Contests with a source silent for this long are stale.
+    pub stale_after: Duration,
 }
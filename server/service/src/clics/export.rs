@@ -0,0 +1,405 @@
+//! Renders the contest in the CLICS Contest API formats,
+//! so the standings can be handed to ICPC and to resolver tools.
+
+use std::collections::HashSet;
+
+use data::annotate_first_solved::annotate_first_solved;
+use data::{Answer, ContestFile, RunTuple, RunsFile, TimeFile, problem_letters};
+use itertools::Itertools;
+use serde::Serialize;
+
+use super::format_reltime;
+
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+pub struct Contest {
+    pub id: String,
+    pub name: String,
+    pub duration: String,
+    pub scoreboard_freeze_duration: String,
+    pub penalty_time: i64,
+}
+
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+pub struct JudgementType {
+    pub id: &'static str,
+    pub name: &'static str,
+    pub penalty: bool,
+    pub solved: bool,
+}
+
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+pub struct Problem {
+    pub id: String,
+    pub label: String,
+    pub name: String,
+    pub ordinal: usize,
+}
+
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+pub struct Team {
+    pub id: String,
+    pub label: String,
+    pub name: String,
+    pub display_name: String,
+    pub affiliation: String,
+}
+
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+pub struct Submission {
+    pub id: String,
+    pub team_id: String,
+    pub problem_id: String,
+    pub contest_time: String,
+}
+
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+pub struct Judgement {
+    pub id: String,
+    pub submission_id: String,
+    pub judgement_type_id: &'static str,
+    pub start_contest_time: String,
+    pub end_contest_time: String,
+}
+
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+pub struct Scoreboard {
+    pub contest_time: String,
+    pub rows: Vec<ScoreboardRow>,
+}
+
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+pub struct ScoreboardRow {
+    pub rank: usize,
+    pub team_id: String,
+    pub score: RowScore,
+    pub problems: Vec<RowProblem>,
+}
+
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+pub struct RowScore {
+    pub num_solved: usize,
+    pub total_time: i64,
+}
+
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+pub struct RowProblem {
+    pub problem_id: String,
+    pub num_judged: usize,
+    pub num_pending: usize,
+    pub solved: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub time: Option<i64>,
+    pub first_to_solve: bool,
+}
+
+/// BOCA only tells if a run was accepted, rejected, or neither.
+/// `X` answers do not count as penalty, so they are exported as compiler errors.
+pub const JUDGEMENT_TYPES: [JudgementType; 3] = [
+    JudgementType {
+        id: "AC",
+        name: "correct",
+        penalty: false,
+        solved: true,
+    },
+    JudgementType {
+        id: "WA",
+        name: "rejected",
+        penalty: true,
+        solved: false,
+    },
+    JudgementType {
+        id: "CE",
+        name: "compiler error",
+        penalty: false,
+        solved: false,
+    },
+];
+
+pub fn contest(contest_id: &str, contest: &ContestFile) -> Contest {
+    Contest {
+        id: contest_id.to_string(),
+        name: contest.contest_name.clone(),
+        duration: format_reltime(contest.maximum_time * 60),
+        scoreboard_freeze_duration: format_reltime(
+            (contest.maximum_time - contest.score_freeze_time) * 60,
+        ),
+        penalty_time: contest.penalty_per_wrong_answer,
+    }
+}
+
+pub fn problems(contest: &ContestFile) -> Vec<Problem> {
+    problem_letters(contest.number_problems)
+        .into_iter()
+        .enumerate()
+        .map(|(ordinal, letter)| Problem {
+            id: letter.to_string(),
+            label: letter.to_string(),
+            name: letter.to_string(),
+            ordinal,
+        })
+        .collect()
+}
+
+pub fn teams(contest: &ContestFile) -> Vec<Team> {
+    contest
+        .teams
+        .values()
+        .map(|team| Team {
+            id: team.login.clone(),
+            label: team.login.clone(),
+            name: team.name.clone(),
+            display_name: team.name.clone(),
+            affiliation: team.escola.clone(),
+        })
+        .collect()
+}
+
+pub fn submissions(runs: &RunsFile) -> Vec<Submission> {
+    runs.sorted()
+        .into_iter()
+        .map(|run| Submission {
+            id: run.id.to_string(),
+            team_id: run.team_login,
+            problem_id: run.prob.to_string(),
+            contest_time: format_reltime(run.time * 60),
+        })
+        .collect()
+}
+
+fn judgement_type_id(answer: &Answer) -> Option<&'static str> {
+    match answer {
+        Answer::Yes { .. } => Some("AC"),
+        Answer::No { .. } => Some("WA"),
+        Answer::Unk { .. } => Some("CE"),
+        Answer::Wait { .. } => None,
+    }
+}
+
+/// Runs that are still waiting, or are hidden by the freeze, have no judgement.
+pub fn judgements(runs: &RunsFile) -> Vec<Judgement> {
+    runs.sorted()
+        .into_iter()
+        .filter_map(|run| {
+            let judgement_type_id = judgement_type_id(&run.answer)?;
+            let time = format_reltime(run.time * 60);
+            Some(Judgement {
+                id: run.id.to_string(),
+                submission_id: run.id.to_string(),
+                judgement_type_id,
+                start_contest_time: time.clone(),
+                end_contest_time: time,
+            })
+        })
+        .collect()
+}
+
+pub fn scoreboard(contest: &ContestFile, runs: &RunsFile, time: TimeFile) -> Scoreboard {
+    let mut contest = contest.clone();
+    let mut runs: Vec<RunTuple> = runs.sorted();
+    annotate_first_solved(&mut HashSet::new(), runs.iter_mut());
+    for run in &runs {
+        contest.apply_run(run);
+    }
+    contest.recalculate_placement();
+
+    let letters = problem_letters(contest.number_problems);
+    let rows = contest
+        .teams
+        .values()
+        .sorted_by_key(|team| team.placement_global)
+        .map(|team| {
+            let score = team.score(contest.scoring);
+            ScoreboardRow {
+                rank: team.placement_global,
+                team_id: team.login.clone(),
+                score: RowScore {
+                    num_solved: score.solved,
+                    total_time: score.penalty,
+                },
+                // Every problem of the contest, even those the team never tried.
+                problems: letters
+                    .iter()
+                    .map(|letter| match team.problems.get(letter) {
+                        Some(problem) => RowProblem {
+                            problem_id: letter.to_string(),
+                            num_judged: problem.submissions,
+                            num_pending: problem.waits.len(),
+                            solved: problem.solved,
+                            time: problem.solved.then_some(problem.time_solved),
+                            first_to_solve: problem.solved_first,
+                        },
+                        None => RowProblem {
+                            problem_id: letter.to_string(),
+                            num_judged: 0,
+                            num_pending: 0,
+                            solved: false,
+                            time: None,
+                            first_to_solve: false,
+                        },
+                    })
+                    .collect(),
+            }
+        })
+        .collect();
+
+    Scoreboard {
+        contest_time: format_reltime(time),
+        rows,
+    }
+}
+
+fn event<T: Serialize>(kind: &str, id: &str, data: &T) -> serde_json::Result<String> {
+    serde_json::to_string(&serde_json::json!({
+        "type": kind,
+        "id": id,
+        "data": data,
+    }))
+}
+
+/// Renders the whole contest as a 2022-07 event feed, one event per line.
+pub fn event_feed(
+    contest_id: &str,
+    contest_file: &ContestFile,
+    runs: &RunsFile,
+) -> serde_json::Result<String> {
+    let mut lines = vec![event(
+        "contest",
+        contest_id,
+        &contest(contest_id, contest_file),
+    )?];
+
+    for judgement_type in &JUDGEMENT_TYPES {
+        lines.push(event("judgement-types", judgement_type.id, judgement_type)?);
+    }
+    for problem in problems(contest_file) {
+        lines.push(event("problems", &problem.id, &problem)?);
+    }
+    for team in teams(contest_file) {
+        lines.push(event("teams", &team.id, &team)?);
+    }
+
+    let judgements = judgements(runs);
+    let mut judgements = judgements.iter().peekable();
+    for submission in submissions(runs) {
+        lines.push(event("submissions", &submission.id, &submission)?);
+        if let Some(judgement) = judgements.next_if(|j| j.submission_id == submission.id) {
+            lines.push(event("judgements", &judgement.id, judgement)?);
+        }
+    }
+
+    let mut feed = lines.join("\n");
+    feed.push('\n');
+    Ok(feed)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::SystemTime;
+
+    use super::*;
+    use crate::clics::EventFeed;
+    use crate::errors::ServiceResult;
+
+    const EVENT_FEED: &str = "../../tests/inputs/clics/event-feed.ndjson";
+
+    fn load() -> ServiceResult<(ContestFile, RunsFile)> {
+        let text = std::fs::read_to_string(EVENT_FEED)?;
+        let state = EventFeed::from_ndjson(&text)?.contest_state(SystemTime::now())?;
+        Ok((state.contest, RunsFile::new(state.runs)))
+    }
+
+    #[test]
+    fn test_scoreboard() -> ServiceResult<()> {
+        let (contest, runs) = load()?;
+
+        let scoreboard = scoreboard(&contest, &runs, 18000);
+        let ranking = scoreboard
+            .rows
+            .iter()
+            .map(|row| (row.rank, row.team_id.as_str(), row.score.clone()))
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            ranking,
+            vec![
+                (
+                    1,
+                    "teambrsc1",
+                    RowScore {
+                        num_solved: 1,
+                        total_time: 45
+                    }
+                ),
+                (
+                    2,
+                    "teambrsc2",
+                    RowScore {
+                        num_solved: 1,
+                        total_time: 70
+                    }
+                ),
+                (
+                    3,
+                    "teambrsp1",
+                    RowScore {
+                        num_solved: 1,
+                        total_time: 305
+                    }
+                ),
+            ]
+        );
+        assert!(scoreboard.rows[0].problems[0].first_to_solve);
+        assert_eq!(scoreboard.rows[1].problems[2].num_pending, 1);
+        for row in &scoreboard.rows {
+            assert_eq!(
+                row.problems
+                    .iter()
+                    .map(|p| p.problem_id.as_str())
+                    .collect::<Vec<_>>(),
+                problems(&contest)
+                    .iter()
+                    .map(|p| p.id.as_str())
+                    .collect::<Vec<_>>()
+            );
+        }
+        let untried = &scoreboard.rows[2].problems[1];
+        assert_eq!((untried.num_judged, untried.solved), (0, false));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_frozen_runs_have_no_judgement() -> ServiceResult<()> {
+        let (contest, runs) = load()?;
+        let frozen = runs.filter_frozen(contest.score_freeze_time);
+
+        assert_eq!(judgements(&runs).len(), 6);
+        assert_eq!(judgements(&frozen).len(), 4);
+        assert_eq!(submissions(&frozen).len(), 9);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_event_feed_round_trip() -> ServiceResult<()> {
+        let (contest, runs) = load()?;
+
+        let feed = event_feed("test", &contest, &runs)?;
+        let state = EventFeed::from_ndjson(&feed)?.contest_state(SystemTime::now())?;
+
+        assert_eq!(state.contest.number_problems, contest.number_problems);
+        assert_eq!(state.contest.score_freeze_time, contest.score_freeze_time);
+        assert_eq!(
+            state.contest.teams.keys().collect::<Vec<_>>(),
+            contest.teams.keys().collect::<Vec<_>>()
+        );
+        let judged = |runs: &[RunTuple]| {
+            runs.iter()
+                .map(|r| (r.time, r.team_login.clone(), judgement_type_id(&r.answer)))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(judged(&state.runs), judged(&runs.sorted()));
+
+        Ok(())
+    }
+}
@@ -1,6 +1,13 @@
 use crate::errors::{Error, ServiceResult};
+use crate::membroadcast;
+use crate::overrides::{Overrides, RunOverride};
+use crate::snapshot::Snapshot;
+use arc_swap::ArcSwap;
+use data::sequenced::Sequenced;
 use data::*;
 use html_escape::decode_html_entities_to_string;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 use tracing::{Level, instrument};
 
 pub trait FromString {
@@ -120,9 +127,104 @@ impl FromString for RunsFile {
 #[derive(Debug)]
 pub struct DB {
     run_file: RunsFile,
+    /// Runs as read from the source, before the overrides.
+    source_runs: RunsFile,
+    pub overrides: Overrides,
+    /// Sequence of the last update pushed to the server, for delta updates.
+    pub sequence: Option<u64>,
     pub run_file_secret: RunsFile,
     pub contest_file_begin: ContestFile,
     pub time_file: TimeFile,
+    published: Arc<PublishedView>,
+}
+
+#[derive(Debug)]
+/// An immutable view of the contest, published by the DB after each change.
+pub struct DbView {
+    /// Increases with every view published.
+    pub version: u64,
+    pub time_file: TimeFile,
+    pub contest_file_begin: ContestFile,
+    /// The runs everyone sees, frozen at the score freeze.
+    pub run_file: RunsFile,
+    pub run_file_secret: RunsFile,
+}
+
+impl DbView {
+    pub fn all_runs(&self) -> Vec<RunTuple> {
+        self.run_file.sorted()
+    }
+}
+
+#[derive(Debug)]
+/// The last view published by the DB.
+/// The DB builds each view on its own, and swaps it in atomically,
+/// so readers never wait for an update to finish.
+pub struct PublishedView {
+    current: ArcSwap<DbView>,
+    freshness: ArcSwap<Freshness>,
+}
+
+#[derive(Debug, Default, Clone, Copy)]
+struct Freshness {
+    /// When the source last updated the contest, or it was restored.
+    last_update: Option<Instant>,
+    /// The source has been silent for too long.
+    stale: bool,
+}
+
+impl PublishedView {
+    pub fn load(&self) -> Arc<DbView> {
+        self.current.load_full()
+    }
+
+    fn store(&self, view: DbView) {
+        self.current.store(Arc::new(view));
+    }
+
+    /// Time since the source last updated the contest, if it ever did.
+    pub fn age(&self) -> Option<Duration> {
+        self.freshness
+            .load()
+            .last_update
+            .map(|last_update| last_update.elapsed())
+    }
+
+    pub fn is_stale(&self) -> bool {
+        self.freshness.load().stale
+    }
+
+    /// Changes the freshness, only from the DB, that has a single writer.
+    fn update_freshness<R>(&self, update: impl FnOnce(&mut Freshness) -> R) -> R {
+        let mut freshness = **self.freshness.load();
+        let result = update(&mut freshness);
+        self.freshness.store(Arc::new(freshness));
+        result
+    }
+}
+
+/// Whether two reads of the contest are the same, ignoring the ids generated for each team read.
+fn same_contest(a: &ContestFile, b: &ContestFile) -> bool {
+    a.contest_name == b.contest_name
+        && a.current_time == b.current_time
+        && a.maximum_time == b.maximum_time
+        && a.score_freeze_time == b.score_freeze_time
+        && a.penalty_per_wrong_answer == b.penalty_per_wrong_answer
+        && a.number_problems == b.number_problems
+        && a.scoring == b.scoring
+        && a.teams.len() == b.teams.len()
+        && a.teams
+            .values()
+            .zip(b.teams.values())
+            .all(|(a, b)| a.login == b.login && a.escola == b.escola && a.name == b.name)
+}
+
+/// Replaces a run removed from the scoreboard, for the clients that already have it.
+fn tombstone(run: RunTuple) -> RunTuple {
+    RunTuple {
+        answer: Answer::Unk { run_id: run.id },
+        ..run
+    }
 }
 
 pub fn read_contest(s: &str) -> ServiceResult<ContestFile> {
@@ -144,10 +246,56 @@ impl DB {
     pub fn empty() -> Self {
         DB {
             run_file: RunsFile::empty(),
+            source_runs: RunsFile::empty(),
+            overrides: Overrides::default(),
+            sequence: None,
             run_file_secret: RunsFile::empty(),
             contest_file_begin: ContestFile::dummy(),
             time_file: 0,
+            published: DB::unpublished(),
         }
+        .published()
+    }
+
+    fn unpublished() -> Arc<PublishedView> {
+        Arc::new(PublishedView {
+            current: ArcSwap::from_pointee(DbView {
+                version: 0,
+                time_file: 0,
+                contest_file_begin: ContestFile::dummy(),
+                run_file: RunsFile::empty(),
+                run_file_secret: RunsFile::empty(),
+            }),
+            freshness: ArcSwap::default(),
+        })
+    }
+
+    /// Publishes the view of the DB as it is, and returns it.
+    fn published(self) -> Self {
+        self.publish();
+        self
+    }
+
+    /// Publishes the view of a DB restored from a snapshot, as fresh as if the source had just updated it.
+    fn restored(mut self) -> Self {
+        self.mark_updated();
+        self.published()
+    }
+
+    fn publish(&self) {
+        let version = self.published.load().version + 1;
+        self.published.store(DbView {
+            version,
+            time_file: self.time_file,
+            contest_file_begin: self.contest_file_begin.clone(),
+            run_file: self.run_file.clone(),
+            run_file_secret: self.run_file_secret.clone(),
+        });
+    }
+
+    /// The views published by this DB, for the readers.
+    pub fn view(&self) -> Arc<PublishedView> {
+        self.published.clone()
     }
 
     pub fn refresh_db(
@@ -156,20 +304,170 @@ impl DB {
         contest: ContestFile,
         mut runs: RunsFile,
     ) -> ServiceResult<Vec<RunTuple>> {
+        let changed = time != self.time_file || !same_contest(&contest, &self.contest_file_begin);
         self.time_file = time;
         self.contest_file_begin = contest;
+        self.mark_updated();
 
         runs.filter_teams(&self.contest_file_begin);
+        self.source_runs = runs;
+
+        Ok(self.apply_overrides(changed))
+    }
+
+    /// Applies the runs that are new or changed since the last refresh.
+    pub fn refresh_delta(
+        &mut self,
+        time: i64,
+        runs: Vec<RunTuple>,
+    ) -> ServiceResult<Vec<RunTuple>> {
+        // The time file is in seconds, the contest time in minutes.
+        let current_time = time.div_euclid(60);
+        let changed =
+            time != self.time_file || current_time != self.contest_file_begin.current_time;
+        self.time_file = time;
+        self.contest_file_begin.current_time = current_time;
+        self.mark_updated();
+
+        for run in runs
+            .iter()
+            .filter(|run| self.contest_file_begin.teams.contains_key(&run.team_login))
+        {
+            self.source_runs.refresh_1(run);
+        }
+
+        Ok(self.apply_overrides(changed))
+    }
+
+    /// Records an update from the source, even one that changed nothing.
+    /// Returns whether the contest was stale until now.
+    pub fn mark_updated(&mut self) -> bool {
+        self.published.update_freshness(|freshness| {
+            freshness.last_update = Some(Instant::now());
+            std::mem::take(&mut freshness.stale)
+        })
+    }
+
+    /// Time since the source last updated the contest, if it ever did.
+    pub fn age(&self) -> Option<Duration> {
+        self.published.age()
+    }
+
+    pub fn is_stale(&self) -> bool {
+        self.published.is_stale()
+    }
+
+    /// Marks the contest as stale, or not. Returns whether that changed.
+    pub fn set_stale(&mut self, stale: bool) -> bool {
+        self.published
+            .update_freshness(|freshness| std::mem::replace(&mut freshness.stale, stale) != stale)
+    }
+
+    /// Applies the overrides to the runs of the source.
+    /// The view is published only if the runs changed, or the contest did, as told by `changed`.
+    fn apply_overrides(&mut self, changed: bool) -> Vec<RunTuple> {
+        let runs = self.overrides.apply(&self.source_runs);
         let runs_frozen = runs.filter_frozen(self.contest_file_begin.score_freeze_time);
 
-        let fresh = self.run_file.refresh(runs_frozen.sorted());
+        let mut fresh = self.run_file.refresh(runs_frozen.sorted());
+        // Refreshing never removes runs, so the hidden ones are replaced
+        // by a tombstone under the same id, that scores nothing.
+        fresh.extend(
+            self.run_file
+                .sorted()
+                .into_iter()
+                .filter(|run| runs_frozen.get(run.id).is_none())
+                .map(tombstone),
+        );
+        // Runs after the freeze change only the secret runs.
+        let changed = changed || !fresh.is_empty() || runs != self.run_file_secret;
+        self.run_file = runs_frozen;
         self.run_file_secret = runs;
+        if changed {
+            self.publish();
+        }
+
+        fresh
+    }
+
+    /// Sets, or removes, the override of a run.
+    /// Returns the runs that changed for the clients.
+    pub fn set_override(
+        &mut self,
+        run_id: i64,
+        change: Option<RunOverride>,
+        author: String,
+        reason: String,
+    ) -> ServiceResult<Vec<RunTuple>> {
+        if self.source_runs.get(run_id).is_none() {
+            return Err(Error::UnknownRun(run_id));
+        }
+
+        self.overrides.set(run_id, change, author, reason);
+        Ok(self.apply_overrides(false))
+    }
+
+    /// Runs as read from the source, with their overrides.
+    pub fn source_runs(&self) -> Vec<(RunTuple, Option<RunOverride>)> {
+        self.source_runs
+            .sorted()
+            .into_iter()
+            .map(|run| {
+                let run_override = self.overrides.get(run.id);
+                (run, run_override)
+            })
+            .collect()
+    }
 
-        Ok(fresh)
+    pub fn snapshot(&self, history: Vec<Sequenced<RunTuple>>) -> Snapshot {
+        Snapshot {
+            time_file: self.time_file,
+            contest: self.contest_file_begin.clone(),
+            runs: self.run_file.clone(),
+            runs_secret: self.run_file_secret.clone(),
+            history,
+            source_runs: Some(self.source_runs.clone()),
+            overrides: self.overrides.clone(),
+        }
+    }
+
+    /// Rebuilds the DB from a snapshot.
+    ///
+    /// The runs in the snapshot history are memoized in `runs_tx` as already sent,
+    /// so they are replayed to new subscribers but never broadcast twice.
+    pub fn from_snapshot(
+        Snapshot {
+            time_file,
+            contest,
+            runs,
+            runs_secret,
+            history,
+            source_runs,
+            overrides,
+        }: Snapshot,
+        runs_tx: &membroadcast::Sender<RunTuple>,
+    ) -> Self {
+        runs_tx.restore(history);
+
+        DB {
+            run_file: runs,
+            // Snapshots saved before the overrides have the source runs as the secret runs.
+            source_runs: source_runs.unwrap_or_else(|| runs_secret.clone()),
+            overrides,
+            sequence: None,
+            run_file_secret: runs_secret,
+            contest_file_begin: contest,
+            time_file,
+            published: DB::unpublished(),
+        }
+        .restored()
     }
 
     pub fn timer_data(&self) -> TimerData {
-        TimerData::new(self.time_file, self.contest_file_begin.score_freeze_time)
+        TimerData {
+            stale: self.is_stale(),
+            ..TimerData::new(self.time_file, self.contest_file_begin.score_freeze_time)
+        }
     }
 
     pub fn all_runs(&self) -> Vec<RunTuple> {
@@ -182,6 +480,8 @@ mod tests {
     use std::fs::File;
     use std::io::{self, Read};
 
+    use data::test_util::run;
+
     use super::*;
     // use data::revelation::RevelationDriver;
 
@@ -205,6 +505,153 @@ mod tests {
         Ok(s)
     }
 
+    #[test]
+    fn test_override_survives_refresh() -> ServiceResult<()> {
+        let team = Team::new("team1", "escola", "Time 1".to_string());
+        let contest = ContestFile::new("c".to_string(), vec![team], 10, 300, 240, 20, 1);
+        let run = run(1, "team1", 5, Answer::No { run_id: 1 });
+
+        let mut db = DB::empty();
+        db.refresh_db(10, contest.clone(), RunsFile::new(vec![run.clone()]))?;
+
+        let fresh = db.set_override(1, Some(RunOverride::Hidden), "juiz".into(), "".into())?;
+        assert_eq!(fresh, vec![tombstone(run.clone())]);
+        assert!(db.all_runs().is_empty());
+
+        let fresh = db.refresh_db(11, contest, RunsFile::new(vec![run]))?;
+        assert!(fresh.is_empty());
+        assert!(db.all_runs().is_empty());
+        assert!(db.run_file_secret.is_empty());
+        assert_eq!(db.source_runs().len(), 1);
+
+        assert!(matches!(
+            db.set_override(2, None, "juiz".into(), "".into()),
+            Err(Error::UnknownRun(2))
+        ));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_hidden_run_leaves_history() -> ServiceResult<()> {
+        let team = Team::new("team1", "escola", "Time 1".to_string());
+        let contest = ContestFile::new("c".to_string(), vec![team], 10, 300, 240, 20, 1);
+        let run = run(1, "team1", 5, Answer::No { run_id: 1 });
+
+        let shared_db = Arc::new(tokio::sync::Mutex::new(DB::empty()));
+        let (runs_tx, _) = membroadcast::channel(10);
+        let (time_tx, _) = tokio::sync::broadcast::channel(10);
+        let state = data::contest_state::ContestState {
+            contest: contest.clone(),
+            runs: vec![run],
+            time: 10,
+        };
+        crate::dbupdate_v2::update_runs_from_data(
+            state, None, &shared_db, &runs_tx, &time_tx, None,
+        )
+        .await?;
+        crate::dbupdate_v2::override_run(
+            1,
+            Some(RunOverride::Hidden),
+            "juiz".into(),
+            "".into(),
+            &shared_db,
+            &runs_tx,
+            None,
+        )
+        .await?;
+
+        let history = runs_tx.history();
+        assert_eq!(history.len(), 1);
+        assert_eq!(history[0].value.answer, Answer::Unk { run_id: 1 });
+
+        let mut replayed = contest;
+        for message in &history {
+            replayed.apply_run(&message.value);
+        }
+        let team = &replayed.teams["team1"];
+        assert!(team.problems.values().all(|p| p.submissions == 0));
+        Ok(())
+    }
+
+    #[test]
+    fn test_refresh_delta_time_in_minutes() -> ServiceResult<()> {
+        let contest = ContestFile::new("c".to_string(), vec![], 10, 300, 240, 20, 1);
+
+        let mut db = DB::empty();
+        db.refresh_db(600, contest, RunsFile::empty())?;
+
+        db.refresh_delta(7265, vec![])?;
+        assert_eq!(db.time_file, 7265);
+        assert_eq!(db.contest_file_begin.current_time, 121);
+        Ok(())
+    }
+
+    #[test]
+    fn test_published_view() -> ServiceResult<()> {
+        let contest = ContestFile::new("c".to_string(), vec![], 10, 300, 240, 20, 1);
+
+        let mut db = DB::empty();
+        let published = db.view();
+        let before = published.load();
+
+        db.refresh_db(42, contest, RunsFile::empty())?;
+        let after = published.load();
+
+        assert!(after.version > before.version);
+        assert_eq!(after.time_file, 42);
+        assert_eq!(before.time_file, 0);
+        Ok(())
+    }
+
+    #[test]
+    fn test_publish_only_changes() -> ServiceResult<()> {
+        let read_contest = || {
+            let team = Team::new("team1", "escola", "Time 1".to_string());
+            ContestFile::new("c".to_string(), vec![team], 10, 300, 240, 20, 1)
+        };
+        let run = run(1, "team1", 250, Answer::Wait { run_id: 1 });
+
+        let mut db = DB::empty();
+        let published = db.view();
+        db.refresh_db(42, read_contest(), RunsFile::new(vec![run.clone()]))?;
+        let version = published.load().version;
+
+        db.refresh_db(42, read_contest(), RunsFile::new(vec![run.clone()]))?;
+        assert_eq!(published.load().version, version);
+
+        // Judged after the freeze, only the secret runs change.
+        let judged = RunTuple {
+            answer: Answer::No { run_id: 1 },
+            ..run
+        };
+        let fresh = db.refresh_db(42, read_contest(), RunsFile::new(vec![judged]))?;
+        assert!(fresh.is_empty());
+        assert!(published.load().version > version);
+        Ok(())
+    }
+
+    #[test]
+    fn test_stale_until_refreshed() -> ServiceResult<()> {
+        let contest = ContestFile::new("c".to_string(), vec![], 10, 300, 240, 20, 1);
+
+        let mut db = DB::empty();
+        assert!(db.age().is_none());
+
+        db.refresh_db(10, contest.clone(), RunsFile::empty())?;
+        assert!(db.age().is_some());
+
+        assert!(db.set_stale(true));
+        assert!(!db.set_stale(true));
+        assert!(db.timer_data().stale);
+
+        db.refresh_db(11, contest, RunsFile::empty())?;
+        assert!(!db.is_stale());
+        assert!(!db.timer_data().stale);
+        assert!(!db.view().is_stale());
+        assert!(db.view().age().is_some());
+        Ok(())
+    }
+
     #[test]
     fn test_from_string() -> ServiceResult<()> {
         let x = "375971416299teambrbr3BN";
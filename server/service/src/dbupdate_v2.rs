@@ -1,20 +1,28 @@
 use std::sync::Arc;
-use std::time::Instant;
+use std::time::{Duration, Instant};
 
-use crate::errors::ServiceResult;
-use crate::{DB, membroadcast, webcast};
+use crate::errors::{Error, ServiceResult};
+use crate::overrides::RunOverride;
+use crate::snapshot::SnapshotStore;
+use crate::source::Source;
+use crate::{DB, membroadcast};
 use data::RunsFile;
-use data::contest_state::ContestState;
-use metrics::{counter, histogram};
-use tokio::sync::Mutex;
+use data::contest_state::{ContestDelta, ContestState, UpdateAck};
+use metrics::{counter, gauge, histogram};
 use tokio::sync::broadcast;
+use tokio::sync::{Mutex, MutexGuard};
+use tracing::{info, warn};
 
+/// Replaces the contest with a full update from the source.
+/// `sequence` starts the delta updates that may follow.
 pub async fn update_runs_from_data(
     data: ContestState,
+    sequence: Option<u64>,
     shared_db: &Arc<Mutex<DB>>,
     runs_tx: &membroadcast::Sender<data::RunTuple>,
     time_tx: &broadcast::Sender<data::TimerData>,
-) -> ServiceResult<()> {
+    snapshot_store: Option<&SnapshotStore>,
+) -> ServiceResult<UpdateAck> {
     let ContestState {
         runs,
         time,
@@ -25,7 +33,50 @@ pub async fn update_runs_from_data(
 
     let mut db = shared_db.lock().await;
     let fresh_runs = db.refresh_db(time, contest, RunsFile::new(runs))?;
+    db.sequence = sequence;
+
+    publish(db, fresh_runs, start, runs_tx, time_tx, snapshot_store).await
+}
+
+/// Applies a delta update, if it follows the last update applied.
+pub async fn update_runs_from_delta(
+    ContestDelta {
+        base,
+        sequence,
+        time,
+        runs,
+    }: ContestDelta,
+    shared_db: &Arc<Mutex<DB>>,
+    runs_tx: &membroadcast::Sender<data::RunTuple>,
+    time_tx: &broadcast::Sender<data::TimerData>,
+    snapshot_store: Option<&SnapshotStore>,
+) -> ServiceResult<UpdateAck> {
+    let start = Instant::now();
+
+    let mut db = shared_db.lock().await;
+    if db.sequence != Some(base) {
+        return Err(Error::SequenceMismatch {
+            expected: db.sequence,
+            got: base,
+        });
+    }
 
+    let fresh_runs = db.refresh_delta(time, runs)?;
+    db.sequence = Some(sequence);
+    counter!("update_runs_from_delta_count").increment(1);
+
+    publish(db, fresh_runs, start, runs_tx, time_tx, snapshot_store).await
+}
+
+/// Broadcasts the runs that changed in an update, and saves the snapshot.
+async fn publish(
+    db: MutexGuard<'_, DB>,
+    fresh_runs: Vec<data::RunTuple>,
+    start: Instant,
+    runs_tx: &membroadcast::Sender<data::RunTuple>,
+    time_tx: &broadcast::Sender<data::TimerData>,
+    snapshot_store: Option<&SnapshotStore>,
+) -> ServiceResult<UpdateAck> {
     let fresh_runs_count = fresh_runs.len() as u64;
     for r in fresh_runs {
         runs_tx.send_memo(r.clone());
@@ -36,43 +87,137 @@ pub async fn update_runs_from_data(
     time_tx.send(db.timer_data()).ok();
     histogram!("update_runs_from_data_time").record(delta);
     counter!("update_runs_from_data_fresh_runs").increment(fresh_runs_count);
+
+    let ack = UpdateAck {
+        sequence: db.sequence,
+    };
+
+    if let Some(store) = snapshot_store
+        && fresh_runs_count > 0
+    {
+        let snapshot = db.snapshot(runs_tx.history());
+        drop(db);
+        if let Err(error) = store.save(&snapshot).await {
+            warn!(?error, "failed saving snapshot");
+        }
+    }
+    Ok(ack)
+}
+
+/// Overrides a run, and broadcasts the change as if BOCA had rejudged it.
+/// The override is kept on top of the next refreshes.
+pub async fn override_run(
+    run_id: i64,
+    change: Option<RunOverride>,
+    author: String,
+    reason: String,
+    shared_db: &Arc<Mutex<DB>>,
+    runs_tx: &membroadcast::Sender<data::RunTuple>,
+    snapshot_store: Option<&SnapshotStore>,
+) -> ServiceResult<()> {
+    let mut db = shared_db.lock().await;
+    let fresh_runs = db.set_override(run_id, change, author.clone(), reason.clone())?;
+    info!(run_id, ?change, author, reason, "run override");
+
+    for r in fresh_runs {
+        runs_tx.send_memo(r);
+    }
+
+    if let Some(store) = snapshot_store {
+        let snapshot = db.snapshot(runs_tx.history());
+        drop(db);
+        if let Err(error) = store.save(&snapshot).await {
+            warn!(?error, "failed saving snapshot");
+        }
+    }
     Ok(())
 }
 
+/// Marks the contest as stale when its source is silent for longer than `stale_after`,
+/// and pushes the flag to the clients with the timer.
+pub async fn staleness_watch(
+    shared_db: Arc<Mutex<DB>>,
+    time_tx: broadcast::Sender<data::TimerData>,
+    stale_after: Duration,
+) {
+    let mut interval = tokio::time::interval(Duration::from_secs(1));
+    loop {
+        interval.tick().await;
+
+        let mut db = shared_db.lock().await;
+        let age = db.age();
+        if let Some(age) = age {
+            gauge!("contest_data_age_seconds").set(age.as_secs_f64());
+        }
+
+        if db.set_stale(age.is_some_and(|age| age > stale_after)) {
+            if db.is_stale() {
+                warn!(?age, "source is silent, contest is stale");
+            } else {
+                info!("contest is fresh again");
+            }
+            time_tx.send(db.timer_data()).ok();
+        }
+    }
+}
+
+/// Reads the source forever, waiting longer after failed reads, as set by its policy.
 #[allow(clippy::type_complexity)]
 pub async fn db_update_loop(
-    boca_url: String,
+    source: Source,
     shared_db: Arc<Mutex<DB>>,
     runs_tx: membroadcast::Sender<data::RunTuple>,
     time_tx: broadcast::Sender<data::TimerData>,
+    snapshot_store: Option<SnapshotStore>,
 ) -> ServiceResult<()> {
-    let dur = tokio::time::Duration::new(1, 0);
-    let mut interval = tokio::time::interval(dur);
     loop {
-        interval.tick().await;
-
         let start = Instant::now();
 
-        let data = webcast::load_data_from_url_maybe(&boca_url).await;
+        let data = source.load_if_changed().await;
 
         let delta = start.elapsed();
-        let runs_fetched = data.as_ref().map(|cs| cs.runs.len()).unwrap_or_default() as u64;
+        let content = match &data {
+            Ok(Some(_)) => "changed",
+            Ok(None) => "unchanged",
+            Err(_) => "failed",
+        };
+        let runs_fetched = match &data {
+            Ok(Some(contest_state)) => contest_state.runs.len() as u64,
+            _ => 0,
+        };
 
-        histogram!("load_data_from_url_time").record(delta);
+        histogram!("load_data_from_url_time", "content" => content).record(delta);
         counter!("load_data_from_url_all_new_runs_count").increment(runs_fetched);
 
         match data {
-            Ok(contest_state) => {
-                let result =
-                    update_runs_from_data(contest_state, &shared_db, &runs_tx, &time_tx).await;
-                match result {
-                    Ok(()) => (),
-                    Err(error) => eprintln!("Retrying after error updating runs: \n{}", error),
+            Ok(None) => {
+                // Nothing to diff, the source is still alive.
+                counter!("load_data_from_url_skipped_count").increment(1);
+                let mut db = shared_db.lock().await;
+                if db.mark_updated() {
+                    time_tx.send(db.timer_data()).ok();
+                }
+            }
+            Ok(Some(contest_state)) => {
+                let result = update_runs_from_data(
+                    contest_state,
+                    None,
+                    &shared_db,
+                    &runs_tx,
+                    &time_tx,
+                    snapshot_store.as_ref(),
+                )
+                .await;
+                if let Err(error) = result {
+                    warn!(%error, "retrying after error updating runs");
+                    source.failed(&error);
                 }
             }
             Err(error) => {
-                eprintln!("Retrying after error loading data: \n{}", error);
+                warn!(%error, "retrying after error loading data");
             }
         }
+
+        tokio::time::sleep(source.next_delay()).await;
     }
 }
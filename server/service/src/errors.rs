@@ -37,9 +37,24 @@ pub enum Error {
     #[error(transparent)]
     ConfigParse(#[from] toml::de::Error),
 
+    #[error(transparent)]
+    Json(#[from] serde_json::Error),
+
     #[error("Error::Parse: {0}")]
     Parse(String),
 
     #[error(transparent)]
     BadLetter(#[from] BadLetter),
+
+    #[error("Delta update based on sequence {got}, but the last one applied is {expected:?}")]
+    SequenceMismatch { expected: Option<u64>, got: u64 },
+
+    #[error("Unknown run: {0}")]
+    UnknownRun(i64),
+
+    #[error("Source not ready: {0}")]
+    SourceNotReady(&'static str),
+
+    #[error("Invalid config: {0}")]
+    InvalidConfig(String),
 }
@@ -1,3 +1,30 @@
+use std::time::Duration;
+
 pub struct HttpConfig {
     pub port: u16,
+    pub connection_limits: ConnectionLimits,
+}
+
+#[derive(Debug, Clone, Copy)]
+/// Limits of the long lived connections, websockets and event streams.
+pub struct ConnectionLimits {
+    /// Connections open at the same time, in the whole server.
+    pub max_connections: usize,
+    /// Connections open at the same time, from a single IP.
+    pub max_connections_per_ip: usize,
+    /// Websockets that do not answer pings for this long are closed.
+    pub idle_timeout: Duration,
+    /// Takes the IP from `Forwarded` and `X-Forwarded-For`, when behind a proxy.
+    pub trust_forwarded_for: bool,
+}
+
+impl Default for ConnectionLimits {
+    fn default() -> Self {
+        Self {
+            max_connections: 20_000,
+            max_connections_per_ip: 200,
+            idle_timeout: Duration::from_secs(60),
+            trust_forwarded_for: false,
+        }
+    }
 }
@@ -1,12 +1,17 @@
 pub mod app_config;
+pub mod clics;
 mod dataio;
 pub mod dbupdate_v2;
 pub mod errors;
 pub mod http;
 pub mod membroadcast;
+pub mod overrides;
 pub mod pair_arg;
+pub mod replay;
 pub mod sentry;
+pub mod snapshot;
+pub mod source;
 pub mod volume;
 pub mod webcast;
 
-pub use dataio::DB;
+pub use dataio::{DB, DbView, PublishedView, read_contest};
@@ -1,56 +1,226 @@
+use data::{RunTuple, sequenced::Sequenced};
 use parking_lot::RwLock;
-use std::{collections::VecDeque, sync::Arc};
+use std::{
+    collections::{HashSet, VecDeque},
+    sync::Arc,
+};
 use tokio::sync::broadcast;
+use tracing::debug;
+
+/// Memoized values are compacted to the last value of each key,
+/// so the memo grows with the number of keys, not with the number of updates.
+pub trait MemoKey {
+    fn memo_key(&self) -> i64;
+}
+
+impl MemoKey for RunTuple {
+    fn memo_key(&self) -> i64 {
+        self.id
+    }
+}
+
+/// The memo is compacted when it doubles in size since the last compaction.
+const MIN_COMPACT_LEN: usize = 1024;
 
 pub struct Receiver<T: Clone> {
-    rx: broadcast::Receiver<T>,
-    messages: VecDeque<T>,
+    rx: broadcast::Receiver<Sequenced<T>>,
+    messages: VecDeque<Sequenced<T>>,
+    /// To resubscribe, replaying the memo, when the receiver lags behind the channel.
+    tx: broadcast::WeakSender<Sequenced<T>>,
+    memo: Arc<RwLock<Memo<T>>>,
+    /// The last sequence received, or skipped as already known.
+    last_seq: Option<u64>,
 }
 
-impl<T: Clone> Receiver<T> {
-    fn new(rx: broadcast::Receiver<T>, messages: &Vec<T>) -> Self {
-        let mut deque = VecDeque::new();
-        for m in messages {
-            deque.push_back(m.clone())
+impl<T: Clone + MemoKey> Receiver<T> {
+    fn new(
+        tx: &broadcast::Sender<Sequenced<T>>,
+        memo: &Arc<RwLock<Memo<T>>>,
+        since: Option<u64>,
+    ) -> Self {
+        let locked = memo.read();
+        let messages = locked.latest(since).into();
+        let last_seq = locked.messages.last().map(|m| m.seq).max(since);
+        Self {
+            rx: tx.subscribe(),
+            messages,
+            tx: tx.downgrade(),
+            memo: memo.clone(),
+            last_seq,
+        }
+    }
+
+    /// Subscribes again, and replays from the memo what was missed.
+    /// Returns `false` if the channel is closed.
+    fn resync(&mut self, skipped: u64) -> bool {
+        let Some(tx) = self.tx.upgrade() else {
+            return false;
+        };
+        debug!(skipped, "receiver lagged, replaying from the memo");
+
+        let memo = self.memo.read();
+        self.rx = tx.subscribe();
+        self.messages = memo.latest(self.last_seq).into();
+        true
+    }
+
+    fn received(&mut self, message: Sequenced<T>) -> Sequenced<T> {
+        self.last_seq = Some(message.seq);
+        message
+    }
+
+    pub async fn recv(&mut self) -> Result<Sequenced<T>, broadcast::error::RecvError> {
+        loop {
+            let next = match self.messages.pop_front() {
+                None => self.rx.recv().await,
+                Some(message) => Ok(message),
+            };
+
+            match next {
+                Ok(message) => return Ok(self.received(message)),
+                Err(broadcast::error::RecvError::Lagged(skipped)) if self.resync(skipped) => {}
+                Err(broadcast::error::RecvError::Lagged(_)) => {
+                    return Err(broadcast::error::RecvError::Closed);
+                }
+                Err(err) => return Err(err),
+            }
+        }
+    }
+
+    /// Receives a value only if one is ready, without waiting.
+    pub fn try_recv(&mut self) -> Result<Sequenced<T>, broadcast::error::TryRecvError> {
+        loop {
+            let next = match self.messages.pop_front() {
+                None => self.rx.try_recv(),
+                Some(message) => Ok(message),
+            };
+
+            match next {
+                Ok(message) => return Ok(self.received(message)),
+                Err(broadcast::error::TryRecvError::Lagged(skipped)) if self.resync(skipped) => {}
+                Err(broadcast::error::TryRecvError::Lagged(_)) => {
+                    return Err(broadcast::error::TryRecvError::Closed);
+                }
+                Err(err) => return Err(err),
+            }
         }
+    }
+}
+
+struct Memo<T> {
+    messages: Vec<Sequenced<T>>,
+    next_seq: u64,
+    compacted_len: usize,
+}
+
+impl<T: Clone + MemoKey> Memo<T> {
+    fn new() -> Self {
+        // Sequences start at the startup time, so they keep growing after a restart
+        // and clients resuming from an older sequence get the whole new stream.
+        let next_seq = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_micros() as u64)
+            .unwrap_or_default();
+
         Self {
-            rx,
-            messages: deque,
+            messages: Vec::new(),
+            next_seq,
+            compacted_len: 0,
         }
     }
 
-    pub async fn recv(&mut self) -> Result<T, broadcast::error::RecvError> {
-        let front = self.messages.pop_front();
+    fn push(&mut self, message: Sequenced<T>) {
+        self.next_seq = self.next_seq.max(message.seq + 1);
+        self.messages.push(message);
 
-        match front {
-            None => self.rx.recv().await,
-            Some(message) => Ok(message),
+        if self.messages.len() >= MIN_COMPACT_LEN.max(2 * self.compacted_len) {
+            self.compact();
         }
     }
+
+    /// Keeps only the last value of each key.
+    /// A receiver resuming from any sequence still gets the last value of every key it missed.
+    fn compact(&mut self) {
+        let compacted = self.latest(None);
+
+        self.compacted_len = compacted.len();
+        self.messages = compacted;
+    }
+
+    /// The last value of each key sent after `since`, in the order they were sent.
+    /// Replaced values are never replayed, e.g. a run before it was hidden.
+    fn latest(&self, since: Option<u64>) -> Vec<Sequenced<T>> {
+        let mut seen = HashSet::new();
+        let mut latest = self
+            .messages
+            .iter()
+            .rev()
+            .take_while(|m| since.is_none_or(|since| m.seq > since))
+            .filter(|m| seen.insert(m.value.memo_key()))
+            .cloned()
+            .collect::<Vec<_>>();
+        latest.reverse();
+        latest
+    }
 }
 
 #[derive(Clone)]
 pub struct Sender<T: Clone> {
-    tx: broadcast::Sender<T>,
-    messages: Arc<RwLock<Vec<T>>>,
+    tx: broadcast::Sender<Sequenced<T>>,
+    memo: Arc<RwLock<Memo<T>>>,
 }
 
-impl<T: Clone> Sender<T> {
-    fn new(tx: broadcast::Sender<T>) -> Self {
+impl<T: Clone + MemoKey> Sender<T> {
+    fn new(tx: broadcast::Sender<Sequenced<T>>) -> Self {
         Self {
             tx,
-            messages: Arc::new(RwLock::new(Vec::new())),
+            memo: Arc::new(RwLock::new(Memo::new())),
         }
     }
 
     pub fn send_memo(&self, value: T) -> usize {
-        self.messages.write().push(value.clone());
-        self.tx.send(value).unwrap_or(0)
+        // Sent while holding the memo, so a new receiver never gets a value twice.
+        let mut memo = self.memo.write();
+        let message = Sequenced {
+            seq: memo.next_seq,
+            value,
+        };
+        memo.push(message.clone());
+        self.tx.send(message).unwrap_or(0)
+    }
+
+    /// Memoizes values that were already sent, without broadcasting them again.
+    /// Values without increasing sequences, from older snapshots, are numbered again.
+    pub fn restore(&self, values: impl IntoIterator<Item = Sequenced<T>>) {
+        let mut memo = self.memo.write();
+        let mut last = None;
+        for mut value in values {
+            if last.is_some_and(|last| value.seq <= last) {
+                value.seq = last.unwrap_or_default() + 1;
+            }
+            last = Some(value.seq);
+            memo.push(value);
+        }
+    }
+
+    /// The last value of each key, as replayed to new receivers.
+    pub fn history(&self) -> Vec<Sequenced<T>> {
+        self.memo.read().latest(None)
     }
 
     pub fn subscribe(&self) -> Receiver<T> {
-        let rx = self.tx.subscribe();
-        Receiver::new(rx, &self.messages.read())
+        self.subscribe_since(None)
+    }
+
+    /// Subscribes, replaying only the memoized values after `since`.
+    pub fn subscribe_since(&self, since: Option<u64>) -> Receiver<T> {
+        Receiver::new(&self.tx, &self.memo, since)
+    }
+
+    /// Subscribes to the values sent from now on, without replaying the memo.
+    pub fn subscribe_new(&self) -> Receiver<T> {
+        let last = self.memo.read().messages.last().map(|m| m.seq);
+        self.subscribe_since(last)
     }
 
     #[cfg(test)]
@@ -59,26 +229,31 @@ impl<T: Clone> Sender<T> {
     }
 }
 
-pub fn channel<T: Clone>(capacity: usize) -> (Sender<T>, Receiver<T>) {
-    let (tx, rx) = broadcast::channel(capacity);
+pub fn channel<T: Clone + MemoKey>(capacity: usize) -> (Sender<T>, Receiver<T>) {
+    let (tx, _) = broadcast::channel(capacity);
     let mem_tx = Sender::new(tx);
-    let mem_rx = Receiver::new(rx, &mem_tx.messages.read());
+    let mem_rx = mem_tx.subscribe();
     (mem_tx, mem_rx)
 }
-
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    impl MemoKey for i32 {
+        fn memo_key(&self) -> i64 {
+            *self as i64
+        }
+    }
+
     #[tokio::test]
     async fn test_mem_broadcast() {
         let (tx, mut rx1) = channel(1_000_000);
 
         let t1 = tokio::spawn(async move {
             println!("rx1 started");
-            assert_eq!(rx1.recv().await.unwrap(), 10);
-            assert_eq!(rx1.recv().await.unwrap(), 20);
-            assert_eq!(rx1.recv().await.unwrap(), 30);
+            assert_eq!(rx1.recv().await.unwrap().value, 10);
+            assert_eq!(rx1.recv().await.unwrap().value, 20);
+            assert_eq!(rx1.recv().await.unwrap().value, 30);
             println!("rx1 finished");
         });
 
@@ -90,9 +265,9 @@ mod tests {
         let mut rx2 = tx.subscribe();
         let t2 = tokio::spawn(async move {
             println!("rx2 started");
-            assert_eq!(rx2.recv().await.unwrap(), 10);
-            assert_eq!(rx2.recv().await.unwrap(), 20);
-            assert_eq!(rx2.recv().await.unwrap(), 30);
+            assert_eq!(rx2.recv().await.unwrap().value, 10);
+            assert_eq!(rx2.recv().await.unwrap().value, 20);
+            assert_eq!(rx2.recv().await.unwrap().value, 30);
             println!("rx2 finished");
         });
         assert_eq!(tx.receiver_count_memo(), 2);
@@ -103,9 +278,9 @@ mod tests {
         let mut rx3 = tx.subscribe();
         let t3 = tokio::spawn(async move {
             println!("rx3 started");
-            assert_eq!(rx3.recv().await.unwrap(), 10);
-            assert_eq!(rx3.recv().await.unwrap(), 20);
-            assert_eq!(rx3.recv().await.unwrap(), 30);
+            assert_eq!(rx3.recv().await.unwrap().value, 10);
+            assert_eq!(rx3.recv().await.unwrap().value, 20);
+            assert_eq!(rx3.recv().await.unwrap().value, 30);
             println!("rx3 finished");
         });
 
@@ -117,4 +292,69 @@ mod tests {
         t2.await.expect("t2");
         t3.await.expect("t3");
     }
+
+    #[tokio::test]
+    async fn test_subscribe_since() {
+        let (tx, _) = channel(100);
+        tx.send_memo(10);
+        tx.send_memo(20);
+        let seq = tx.history()[0].seq;
+
+        let mut rx = tx.subscribe_since(Some(seq));
+        tx.send_memo(30);
+
+        let next = rx.recv().await.unwrap();
+        assert_eq!(next.value, 20);
+        assert!(next.seq > seq);
+        assert_eq!(rx.recv().await.unwrap().value, 30);
+        assert!(rx.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_subscribe_new() {
+        let (tx, _) = channel(100);
+        tx.send_memo(10);
+
+        let mut rx = tx.subscribe_new();
+        assert!(rx.try_recv().is_err());
+        tx.send_memo(20);
+        assert_eq!(rx.recv().await.unwrap().value, 20);
+    }
+
+    #[tokio::test]
+    async fn test_lagged_receiver_replays_memo() {
+        let (tx, _) = channel(2);
+        tx.send_memo(1);
+        let mut rx = tx.subscribe();
+        for i in 2..10 {
+            tx.send_memo(i);
+        }
+        tx.send_memo(2);
+
+        let mut received = vec![];
+        while let Ok(message) = rx.try_recv() {
+            received.push(message.value);
+        }
+        assert_eq!(received, vec![1, 3, 4, 5, 6, 7, 8, 9, 2]);
+
+        tx.send_memo(10);
+        assert_eq!(rx.recv().await.unwrap().value, 10);
+    }
+
+    #[test]
+    fn test_memo_is_compacted() {
+        let (tx, _) = channel(10);
+        for i in 0..(2 * MIN_COMPACT_LEN) {
+            tx.send_memo((i % 10) as i32);
+        }
+
+        let history = tx.history();
+        assert!(history.len() < MIN_COMPACT_LEN);
+        assert!(history.windows(2).all(|w| w[0].seq < w[1].seq));
+
+        let mut last = history.iter().map(|m| m.value).collect::<Vec<_>>();
+        last.sort();
+        last.dedup();
+        assert_eq!(last, (0..10).collect::<Vec<_>>());
+    }
 }
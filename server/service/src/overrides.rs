@@ -0,0 +1,143 @@
+use std::collections::BTreeMap;
+
+use data::{Answer, RunTuple, RunsFile};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+/// A correction made by the staff to a run judged by BOCA.
+pub enum RunOverride {
+    Accepted,
+    Rejected,
+    /// The run does not count, it is removed from the scoreboard.
+    Hidden,
+}
+
+impl RunOverride {
+    /// The run after the override, `None` when it is hidden.
+    fn apply(self, mut run: RunTuple) -> Option<RunTuple> {
+        let run_id = run.id;
+        run.answer = match self {
+            RunOverride::Accepted => Answer::Yes {
+                time: run.time,
+                is_first: false,
+                run_id,
+            },
+            RunOverride::Rejected => Answer::No { run_id },
+            RunOverride::Hidden => return None,
+        };
+        Some(run)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+/// Who changed the override of a run, and why.
+pub struct AuditEntry {
+    /// Unix time, in seconds.
+    pub at: u64,
+    pub run_id: i64,
+    /// The new override, `None` when it was removed.
+    pub change: Option<RunOverride>,
+    pub author: String,
+    pub reason: String,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+/// Overrides applied on top of the runs read from BOCA, on every refresh.
+pub struct Overrides {
+    by_run: BTreeMap<i64, RunOverride>,
+    audit: Vec<AuditEntry>,
+}
+
+impl Overrides {
+    pub fn get(&self, run_id: i64) -> Option<RunOverride> {
+        self.by_run.get(&run_id).copied()
+    }
+
+    pub fn audit(&self) -> &[AuditEntry] {
+        &self.audit
+    }
+
+    /// Sets, or removes, the override of a run, and records it in the audit log.
+    pub fn set(
+        &mut self,
+        run_id: i64,
+        change: Option<RunOverride>,
+        author: String,
+        reason: String,
+    ) {
+        match change {
+            Some(run_override) => self.by_run.insert(run_id, run_override),
+            None => self.by_run.remove(&run_id),
+        };
+
+        let at = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or_default();
+
+        self.audit.push(AuditEntry {
+            at,
+            run_id,
+            change,
+            author,
+            reason,
+        });
+    }
+
+    /// The runs after the overrides, without the hidden ones.
+    pub fn apply(&self, runs: &RunsFile) -> RunsFile {
+        RunsFile::new(
+            runs.sorted()
+                .into_iter()
+                .filter_map(|run| match self.get(run.id) {
+                    Some(run_override) => run_override.apply(run),
+                    None => Some(run),
+                })
+                .collect(),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use data::test_util::run;
+
+    use super::*;
+
+    #[test]
+    fn test_overrides_are_audited() {
+        let run = run(7, "team1", 42, Answer::No { run_id: 7 });
+        let runs = RunsFile::new(vec![run.clone()]);
+
+        let mut overrides = Overrides::default();
+        overrides.set(
+            7,
+            Some(RunOverride::Accepted),
+            "juiz".into(),
+            "rejudge".into(),
+        );
+        assert_eq!(
+            overrides.apply(&runs).sorted()[0].answer,
+            Answer::Yes {
+                time: 42,
+                is_first: false,
+                run_id: 7
+            }
+        );
+
+        overrides.set(7, Some(RunOverride::Hidden), "juiz".into(), "".into());
+        assert!(overrides.apply(&runs).is_empty());
+
+        overrides.set(7, None, "juiz".into(), "BOCA fixed it".into());
+        assert_eq!(overrides.apply(&runs).sorted(), vec![run]);
+        assert_eq!(
+            overrides
+                .audit()
+                .iter()
+                .map(|e| e.change)
+                .collect::<Vec<_>>(),
+            vec![Some(RunOverride::Accepted), Some(RunOverride::Hidden), None]
+        );
+    }
+}
@@ -0,0 +1,283 @@
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Instant;
+
+use data::RunTuple;
+use data::contest_state::ContestState;
+use data::{ContestFile, TimeFile};
+use futures::FutureExt;
+use futures::future::BoxFuture;
+use parking_lot::Mutex;
+use tokio::sync::OnceCell;
+
+use crate::errors::ServiceResult;
+use crate::source::{ContestSource, webcast_source};
+
+#[derive(Debug)]
+struct Clock {
+    /// Contest time at `anchor`, in seconds.
+    base: TimeFile,
+    anchor: Instant,
+    speed: f64,
+    paused: bool,
+    /// Score freeze of the recorded contest, in seconds, once it is read.
+    freeze: Option<TimeFile>,
+    /// Contest time of the recorded webcast, where the replay stops.
+    end: Option<TimeFile>,
+}
+
+impl Clock {
+    fn now(&self) -> TimeFile {
+        let now = if self.paused {
+            self.base
+        } else {
+            self.base + (self.anchor.elapsed().as_secs_f64() * self.speed) as TimeFile
+        };
+        self.end.map_or(now, |end| now.min(end))
+    }
+
+    /// Restarts the clock from the current time, before changing how it runs.
+    fn rebase(&mut self) {
+        self.base = self.now();
+        self.anchor = Instant::now();
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+/// Commands that drive the clock of a replay.
+pub enum ReplayCommand {
+    Pause,
+    Resume,
+    /// Jumps forward to a contest minute.
+    Seek(i64),
+    /// Jumps forward to the score freeze.
+    Freeze,
+    Speed(f64),
+    Status,
+}
+
+impl FromStr for ReplayCommand {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut words = s.split_whitespace();
+        let command = match (words.next(), words.next()) {
+            (Some("pause" | "p"), None) => ReplayCommand::Pause,
+            (Some("resume" | "r"), None) => ReplayCommand::Resume,
+            (Some("seek" | "s"), Some(minute)) => ReplayCommand::Seek(
+                minute
+                    .parse()
+                    .map_err(|_| format!("invalid minute: {minute}"))?,
+            ),
+            (Some("freeze" | "f"), None) => ReplayCommand::Freeze,
+            (Some("speed" | "x"), Some(speed)) => match speed.trim_end_matches('x').parse() {
+                Ok(speed) if speed > 0.0 => ReplayCommand::Speed(speed),
+                _ => return Err(format!("invalid speed: {speed}")),
+            },
+            (Some("status" | "?"), None) => ReplayCommand::Status,
+            _ => {
+                return Err(format!(
+                    "unknown command: {s:?}, expected pause, resume, seek MINUTE, freeze, speed X or status"
+                ));
+            }
+        };
+        if words.next().is_some() {
+            return Err(format!("too many arguments: {s:?}"));
+        }
+        Ok(command)
+    }
+}
+
+#[derive(Debug, Clone)]
+/// Drives the clock of a replay, while it runs.
+pub struct ReplayControl(Arc<Mutex<Clock>>);
+
+impl ReplayControl {
+    /// A clock starting at contest time `start`, in seconds, running `speed` times faster.
+    pub fn new(start: TimeFile, speed: f64) -> Self {
+        Self(Arc::new(Mutex::new(Clock {
+            base: start,
+            anchor: Instant::now(),
+            speed,
+            paused: false,
+            freeze: None,
+            end: None,
+        })))
+    }
+
+    /// The contest time of the replay, in seconds.
+    pub fn now(&self) -> TimeFile {
+        self.0.lock().now()
+    }
+
+    /// Applies the command, and describes the clock after it.
+    /// The runs already sent cannot be taken back, so the clock never goes backwards.
+    pub fn apply(&self, command: ReplayCommand) -> Result<String, String> {
+        let mut clock = self.0.lock();
+        let seek = |clock: &mut Clock, to: TimeFile| {
+            if to < clock.now() {
+                return Err("cannot seek backwards, restart the replay instead".to_string());
+            }
+            clock.base = to;
+            clock.anchor = Instant::now();
+            Ok(())
+        };
+
+        match command {
+            ReplayCommand::Pause => {
+                clock.rebase();
+                clock.paused = true;
+            }
+            ReplayCommand::Resume => {
+                clock.rebase();
+                clock.paused = false;
+            }
+            ReplayCommand::Seek(minute) => seek(&mut clock, minute * 60)?,
+            ReplayCommand::Freeze => {
+                let freeze = clock
+                    .freeze
+                    .ok_or_else(|| "the recorded contest was not read yet".to_string())?;
+                seek(&mut clock, freeze)?;
+            }
+            ReplayCommand::Speed(speed) => {
+                clock.rebase();
+                clock.speed = speed;
+            }
+            ReplayCommand::Status => {}
+        }
+
+        let now = clock.now();
+        Ok(format!(
+            "{}{:02}:{:02}:{:02} at {}x{}",
+            if now < 0 { "-" } else { "" },
+            now.abs() / 3600,
+            now.abs() / 60 % 60,
+            now.abs() % 60,
+            clock.speed,
+            if clock.paused { ", paused" } else { "" },
+        ))
+    }
+
+    fn set_bounds(&self, contest: &ContestFile, end: TimeFile) {
+        let mut clock = self.0.lock();
+        clock.freeze = Some(contest.score_freeze_time * 60);
+        clock.end = Some(end);
+    }
+}
+
+#[derive(Debug)]
+struct Recorded {
+    contest: ContestFile,
+    runs: Vec<RunTuple>,
+}
+
+#[derive(Debug)]
+/// Replays a recorded webcast as if it were live.
+/// The runs are released as the clock of the replay reaches their time,
+/// and the score freeze applies as in a live contest.
+pub struct Replay {
+    source: Box<dyn ContestSource>,
+    recorded: OnceCell<Recorded>,
+    control: ReplayControl,
+}
+
+impl Replay {
+    /// Replays the webcast at `uri`, read as in `webcast_source`.
+    pub fn new(uri: &str, control: ReplayControl) -> Self {
+        Self {
+            source: webcast_source(uri),
+            recorded: OnceCell::new(),
+            control,
+        }
+    }
+
+    async fn recorded(&self) -> ServiceResult<&Recorded> {
+        self.recorded
+            .get_or_try_init(|| async {
+                let ContestState {
+                    runs,
+                    time,
+                    contest,
+                } = self.source.load().await?;
+                self.control.set_bounds(&contest, time);
+                Ok(Recorded { contest, runs })
+            })
+            .await
+    }
+}
+
+impl ContestSource for Replay {
+    fn kind(&self) -> &'static str {
+        "replay"
+    }
+
+    fn load(&self) -> BoxFuture<'_, ServiceResult<ContestState>> {
+        async move {
+            let recorded = self.recorded().await?;
+
+            let time = self.control.now();
+            let minute = time.div_euclid(60);
+
+            let mut contest = recorded.contest.clone();
+            contest.current_time = minute;
+            let runs = recorded
+                .runs
+                .iter()
+                .filter(|run| run.time <= minute)
+                .cloned()
+                .collect();
+
+            Ok(ContestState {
+                runs,
+                time,
+                contest,
+            })
+        }
+        .boxed()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WEBCAST: &str = "../../tests/inputs/webcast_jones.zip";
+
+    #[test]
+    fn test_parse_commands() {
+        assert_eq!("p".parse(), Ok(ReplayCommand::Pause));
+        assert_eq!("seek 120".parse(), Ok(ReplayCommand::Seek(120)));
+        assert_eq!("speed 60x".parse(), Ok(ReplayCommand::Speed(60.0)));
+        assert!("speed 0".parse::<ReplayCommand>().is_err());
+        assert!("seek".parse::<ReplayCommand>().is_err());
+        assert!("freeze now".parse::<ReplayCommand>().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_replay_releases_runs() -> ServiceResult<()> {
+        let control = ReplayControl::new(0, 1.0);
+        let replay = Replay::new(WEBCAST, control.clone());
+        control.apply(ReplayCommand::Pause).unwrap();
+
+        let start = replay.load().await?;
+        assert_eq!(start.time, 0);
+        assert!(start.runs.iter().all(|run| run.time <= 0));
+
+        control.apply(ReplayCommand::Freeze).unwrap();
+        let freeze = replay.load().await?;
+        assert_eq!(freeze.time, freeze.contest.score_freeze_time * 60);
+        assert!(freeze.runs.len() > start.runs.len());
+        assert!(control.apply(ReplayCommand::Seek(0)).is_err());
+
+        control.apply(ReplayCommand::Seek(100_000)).unwrap();
+        let end = replay.load().await?;
+        let recorded = load_recorded().await?;
+        assert_eq!(end.time, recorded.time);
+        assert_eq!(end.runs.len(), recorded.runs.len());
+        Ok(())
+    }
+
+    async fn load_recorded() -> ServiceResult<ContestState> {
+        webcast_source(WEBCAST).load().await
+    }
+}
@@ -0,0 +1,143 @@
+use std::path::{Path, PathBuf};
+
+use data::{ContestFile, RunTuple, RunsFile, TimeFile, sequenced::Sequenced};
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
+use tracing::{debug, instrument};
+
+use crate::errors::ServiceResult;
+use crate::overrides::Overrides;
+use crate::{DB, membroadcast};
+
+const SNAPSHOT_FILE: &str = "snapshot.json";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+/// Everything the server needs to resume a contest after a restart.
+pub struct Snapshot {
+    pub time_file: TimeFile,
+    pub contest: ContestFile,
+    pub runs: RunsFile,
+    pub runs_secret: RunsFile,
+    /// Runs already broadcast to the clients, in the order they were sent.
+    pub history: Vec<Sequenced<RunTuple>>,
+    /// Runs as read from the source, before the overrides.
+    #[serde(default)]
+    pub source_runs: Option<RunsFile>,
+    #[serde(default)]
+    pub overrides: Overrides,
+}
+
+#[derive(Debug, Clone)]
+/// A local directory where the contest snapshot is stored.
+pub struct SnapshotStore {
+    dir: PathBuf,
+}
+
+impl SnapshotStore {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// A store in a subdirectory, for a contest with its own source.
+    pub fn contest(&self, name: &str) -> Self {
+        Self::new(self.dir.join(name))
+    }
+
+    /// Loads the last saved snapshot, if there is one.
+    #[instrument(skip(self), fields(dir = ?self.dir), err)]
+    pub async fn load(&self) -> ServiceResult<Option<Snapshot>> {
+        self.load_file(SNAPSHOT_FILE).await
+    }
+
+    /// Loads another file kept next to the snapshot, if there is one.
+    pub async fn load_file<T: DeserializeOwned>(&self, name: &str) -> ServiceResult<Option<T>> {
+        let text = match tokio::fs::read_to_string(self.dir.join(name)).await {
+            Ok(text) => text,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(err) => return Err(err.into()),
+        };
+
+        Ok(Some(serde_json::from_str(&text)?))
+    }
+
+    /// Restores the DB from the last saved snapshot, if there is one.
+    /// Restored runs are memoized in `runs_tx` as already sent.
+    pub async fn restore(
+        &self,
+        runs_tx: &membroadcast::Sender<RunTuple>,
+    ) -> ServiceResult<Option<DB>> {
+        let snapshot = self.load().await?;
+        Ok(snapshot.map(|snapshot| DB::from_snapshot(snapshot, runs_tx)))
+    }
+
+    /// Saves the snapshot, replacing the previous one.
+    #[instrument(skip(self, snapshot), fields(dir = ?self.dir), err)]
+    pub async fn save(&self, snapshot: &Snapshot) -> ServiceResult<()> {
+        self.save_file(SNAPSHOT_FILE, snapshot).await?;
+
+        debug!(runs = snapshot.history.len(), "saved snapshot");
+        Ok(())
+    }
+
+    /// Saves another file next to the snapshot, replacing the previous one.
+    ///
+    /// The file is written to a temporary file first,
+    /// so a crash never leaves a half written file behind.
+    pub async fn save_file<T: Serialize>(&self, name: &str, value: &T) -> ServiceResult<()> {
+        let text = serde_json::to_vec(value)?;
+        let path = self.dir.join(name);
+        let tmp = path.with_extension("json.tmp");
+
+        tokio::fs::create_dir_all(&self.dir).await?;
+        tokio::fs::write(&tmp, text).await?;
+        tokio::fs::rename(&tmp, &path).await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use data::test_util::run;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_snapshot_round_trip() -> ServiceResult<()> {
+        let dir =
+            std::env::temp_dir().join(format!("animeitor-snapshot-{}", rand::random::<u64>()));
+        let store = SnapshotStore::new(&dir);
+
+        assert!(store.load().await?.is_none());
+
+        let run = run(1, "team1", 10, data::Answer::No { run_id: 1 });
+        let (runs_tx, _) = membroadcast::channel(10);
+        runs_tx.send_memo(run.clone());
+
+        let db = DB::empty();
+        store.save(&db.snapshot(runs_tx.history())).await?;
+
+        let snapshot = store
+            .load()
+            .await?
+            .expect("snapshot should have been saved");
+        assert_eq!(
+            snapshot
+                .history
+                .into_iter()
+                .map(|m| m.value)
+                .collect::<Vec<_>>(),
+            vec![run]
+        );
+        assert_eq!(snapshot.time_file, db.time_file);
+
+        let (restored_tx, _) = membroadcast::channel(10);
+        store.restore(&restored_tx).await?;
+        assert_eq!(restored_tx.history().len(), 1);
+
+        tokio::fs::remove_dir_all(dir).await?;
+        Ok(())
+    }
+}
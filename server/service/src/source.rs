@@ -0,0 +1,489 @@
+use std::fmt::Debug;
+use std::io::BufRead;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use data::configdata::{ConfigContest, PollPolicy, unix_now};
+use data::contest_state::ContestState;
+use futures::FutureExt;
+use futures::future::BoxFuture;
+use parking_lot::Mutex;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use tokio::sync::watch;
+
+use crate::clics::load_data_from_event_feed;
+use crate::errors::{Error, ServiceResult};
+use crate::webcast::{
+    Validators, is_url, read_bytes_from_file, read_bytes_from_url, read_bytes_from_url_if_modified,
+    read_webcast_dir, read_webcast_zip,
+};
+
+/// Reads the whole contest state from somewhere.
+/// New kinds of sources implement this, and are read by `Source`.
+pub trait ContestSource: Debug + Send + Sync {
+    /// Short name of the kind of source, shown in the status.
+    fn kind(&self) -> &'static str;
+
+    fn load(&self) -> BoxFuture<'_, ServiceResult<ContestState>>;
+
+    /// Reads the contest state, or `None` when it did not change since the last read.
+    /// Sources that cannot tell read it every time.
+    fn load_if_changed(&self) -> BoxFuture<'_, ServiceResult<Option<ContestState>>> {
+        self.load().map(|result| result.map(Some)).boxed()
+    }
+
+    /// Forgets what was read, so the next read is never skipped.
+    /// Called when the last read could not be applied.
+    fn forget(&self) {}
+}
+
+#[derive(Debug, Default)]
+/// The hash of the last content read, to skip parsing it again.
+struct LastHash(Mutex<Option<Vec<u8>>>);
+
+impl LastHash {
+    /// Whether the content differs from the last one, which it replaces.
+    fn changed(&self, parts: &[&[u8]]) -> bool {
+        let mut hasher = Sha256::new();
+        for part in parts {
+            hasher.update((part.len() as u64).to_le_bytes());
+            hasher.update(part);
+        }
+        let hash = hasher.finalize().to_vec();
+        self.0.lock().replace(hash.clone()) != Some(hash)
+    }
+
+    fn forget(&self) {
+        self.0.lock().take();
+    }
+}
+
+#[derive(Debug)]
+/// BOCA's webcast zip, downloaded from BOCA.
+/// Asks for it with `If-None-Match` and `If-Modified-Since`, and skips parsing unchanged zips.
+pub struct BocaHttpZip {
+    url: String,
+    client: reqwest::Client,
+    validators: Mutex<Validators>,
+    last_hash: LastHash,
+}
+
+impl BocaHttpZip {
+    pub fn new(url: String) -> Self {
+        Self {
+            url,
+            client: reqwest::Client::new(),
+            validators: Mutex::default(),
+            last_hash: LastHash::default(),
+        }
+    }
+}
+
+impl ContestSource for BocaHttpZip {
+    fn kind(&self) -> &'static str {
+        "boca_http_zip"
+    }
+
+    fn load(&self) -> BoxFuture<'_, ServiceResult<ContestState>> {
+        async move { read_webcast_zip(&read_bytes_from_url(&self.url).await?) }.boxed()
+    }
+
+    fn load_if_changed(&self) -> BoxFuture<'_, ServiceResult<Option<ContestState>>> {
+        async move {
+            let validators = self.validators.lock().clone();
+            let Some((zip_data, validators)) =
+                read_bytes_from_url_if_modified(&self.client, &self.url, &validators).await?
+            else {
+                return Ok(None);
+            };
+            *self.validators.lock() = validators;
+
+            if !self.last_hash.changed(&[&zip_data]) {
+                return Ok(None);
+            }
+            read_webcast_zip(&zip_data).map(Some)
+        }
+        .boxed()
+    }
+
+    fn forget(&self) {
+        *self.validators.lock() = Validators::default();
+        self.last_hash.forget();
+    }
+}
+
+#[derive(Debug, Default)]
+/// BOCA's webcast zip, in the local filesystem.
+pub struct LocalZip {
+    path: String,
+    last_hash: LastHash,
+}
+
+impl LocalZip {
+    pub fn new(path: String) -> Self {
+        Self {
+            path,
+            last_hash: LastHash::default(),
+        }
+    }
+}
+
+impl ContestSource for LocalZip {
+    fn kind(&self) -> &'static str {
+        "local_zip"
+    }
+
+    fn load(&self) -> BoxFuture<'_, ServiceResult<ContestState>> {
+        async move { read_webcast_zip(&read_bytes_from_file(&self.path).await?) }.boxed()
+    }
+
+    fn load_if_changed(&self) -> BoxFuture<'_, ServiceResult<Option<ContestState>>> {
+        async move {
+            let zip_data = read_bytes_from_file(&self.path).await?;
+            if !self.last_hash.changed(&[&zip_data]) {
+                return Ok(None);
+            }
+            read_webcast_zip(&zip_data).map(Some)
+        }
+        .boxed()
+    }
+
+    fn forget(&self) {
+        self.last_hash.forget();
+    }
+}
+
+#[derive(Debug, Default)]
+/// BOCA's webcast files, extracted to a directory.
+pub struct LocalDirectory {
+    dir: PathBuf,
+    last_hash: LastHash,
+}
+
+impl LocalDirectory {
+    pub fn new(dir: PathBuf) -> Self {
+        Self {
+            dir,
+            last_hash: LastHash::default(),
+        }
+    }
+}
+
+impl ContestSource for LocalDirectory {
+    fn kind(&self) -> &'static str {
+        "local_directory"
+    }
+
+    fn load(&self) -> BoxFuture<'_, ServiceResult<ContestState>> {
+        async move { read_webcast_dir(&self.dir).await?.parse() }.boxed()
+    }
+
+    fn load_if_changed(&self) -> BoxFuture<'_, ServiceResult<Option<ContestState>>> {
+        async move {
+            let files = read_webcast_dir(&self.dir).await?;
+            let parts = [&files.time, &files.contest, &files.runs].map(|file| file.as_bytes());
+            if !self.last_hash.changed(&parts) {
+                return Ok(None);
+            }
+            files.parse().map(Some)
+        }
+        .boxed()
+    }
+
+    fn forget(&self) {
+        self.last_hash.forget();
+    }
+}
+
+#[derive(Debug, Clone)]
+/// CLICS Contest API event feed, from an url or a local file.
+pub struct EventFeed {
+    pub uri: String,
+}
+
+impl ContestSource for EventFeed {
+    fn kind(&self) -> &'static str {
+        "event_feed"
+    }
+
+    fn load(&self) -> BoxFuture<'_, ServiceResult<ContestState>> {
+        load_data_from_event_feed(&self.uri).boxed()
+    }
+}
+
+#[derive(Debug)]
+/// Contest states piped to stdin, one JSON `ContestState` per line.
+/// Each read gives the last state received, and reads skip it until a new line arrives.
+pub struct StdinPipe {
+    latest: Mutex<watch::Receiver<Option<String>>>,
+}
+
+impl StdinPipe {
+    /// Starts reading stdin, in a thread of its own.
+    pub fn new() -> Self {
+        let (tx, latest) = watch::channel(None);
+        std::thread::spawn(move || {
+            for line in std::io::stdin().lock().lines() {
+                match line {
+                    Ok(line) if line.trim().is_empty() => continue,
+                    Ok(line) => {
+                        if tx.send(Some(line)).is_err() {
+                            break;
+                        }
+                    }
+                    Err(error) => {
+                        tracing::warn!(?error, "failed reading stdin");
+                        break;
+                    }
+                }
+            }
+            tracing::info!("stdin closed");
+        });
+        Self {
+            latest: Mutex::new(latest),
+        }
+    }
+
+    fn parse(line: Option<String>) -> ServiceResult<ContestState> {
+        match line {
+            Some(line) => Ok(serde_json::from_str(&line)?),
+            None => Err(Error::SourceNotReady("nothing read from stdin yet")),
+        }
+    }
+}
+
+impl Default for StdinPipe {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ContestSource for StdinPipe {
+    fn kind(&self) -> &'static str {
+        "stdin"
+    }
+
+    fn load(&self) -> BoxFuture<'_, ServiceResult<ContestState>> {
+        let line = self.latest.lock().borrow().clone();
+        async move { Self::parse(line) }.boxed()
+    }
+
+    fn load_if_changed(&self) -> BoxFuture<'_, ServiceResult<Option<ContestState>>> {
+        let line = {
+            let mut latest = self.latest.lock();
+            // Still read after stdin is closed, so its last line is not lost.
+            let line = latest.borrow_and_update();
+            line.has_changed().then(|| line.clone())
+        };
+        async move { line.map(Self::parse).transpose() }.boxed()
+    }
+
+    fn forget(&self) {
+        self.latest.lock().mark_changed();
+    }
+}
+
+/// Picks the source of BOCA's webcast by the uri:
+/// `-` is stdin, urls are downloaded, directories hold the extracted files,
+/// and anything else is a zip file.
+pub fn webcast_source(uri: &str) -> Box<dyn ContestSource> {
+    if uri == "-" {
+        Box::new(StdinPipe::new())
+    } else if is_url(uri) {
+        Box::new(BocaHttpZip::new(uri.to_string()))
+    } else if std::path::Path::new(uri).is_dir() {
+        Box::new(LocalDirectory::new(uri.into()))
+    } else {
+        Box::new(LocalZip::new(uri.to_string()))
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+/// A failed read of a source.
+pub struct SourceError {
+    /// Unix time of the failure.
+    pub at: u64,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+/// How the reads of a source are going.
+pub struct SourceHealth {
+    pub kind: &'static str,
+    /// Unix time of the last successful read.
+    pub last_success: Option<u64>,
+    pub last_error: Option<SourceError>,
+    /// Time taken by the last read, in milliseconds.
+    pub latency_ms: Option<u64>,
+    /// Failed reads since the last successful one.
+    pub failures: u32,
+}
+
+#[derive(Debug, Clone)]
+/// Where the contest state is read from, and how often.
+pub struct Source {
+    reader: Arc<dyn ContestSource>,
+    pub policy: PollPolicy,
+    health: Arc<Mutex<SourceHealth>>,
+}
+
+impl Source {
+    pub fn new(reader: Box<dyn ContestSource>) -> Self {
+        let health = SourceHealth {
+            kind: reader.kind(),
+            ..Default::default()
+        };
+        Self {
+            reader: reader.into(),
+            policy: PollPolicy::default(),
+            health: Arc::new(Mutex::new(health)),
+        }
+    }
+
+    pub fn with_policy(self, policy: PollPolicy) -> Self {
+        Self { policy, ..self }
+    }
+
+    /// BOCA's webcast, see `webcast_source`.
+    pub fn webcast(uri: &str) -> Self {
+        Self::new(webcast_source(uri))
+    }
+
+    pub fn event_feed(uri: &str) -> Self {
+        Self::new(Box::new(EventFeed {
+            uri: uri.to_string(),
+        }))
+    }
+
+    /// The source of a contest that is not read from the server's main source.
+    pub fn from_config(config: &ConfigContest) -> Option<Self> {
+        let source = match (&config.boca_url, &config.event_feed) {
+            (Some(url), _) => Source::webcast(url),
+            (None, Some(event_feed)) => Source::event_feed(event_feed),
+            (None, None) => return None,
+        };
+        Some(source.with_policy(config.poll.unwrap_or_default()))
+    }
+
+    /// Reads the contest state, keeping track of the health of the source.
+    pub async fn load(&self) -> ServiceResult<ContestState> {
+        let start = Instant::now();
+        let result = self.reader.load().await;
+        self.record(start, &result);
+        result
+    }
+
+    /// Reads the contest state, or `None` when it did not change since the last read.
+    pub async fn load_if_changed(&self) -> ServiceResult<Option<ContestState>> {
+        let start = Instant::now();
+        let result = self.reader.load_if_changed().await;
+        if result.is_err() {
+            self.reader.forget();
+        }
+        self.record(start, &result);
+        result
+    }
+
+    fn record<T>(&self, start: Instant, result: &ServiceResult<T>) {
+        let mut health = self.health.lock();
+        health.latency_ms = Some(start.elapsed().as_millis() as u64);
+        match result {
+            Ok(_) => {
+                health.last_success = Some(unix_now());
+                health.failures = 0;
+            }
+            Err(error) => record_failure(&mut health, error),
+        }
+    }
+
+    /// Records a failure found after the read, such as contest data that does not add up.
+    /// The next read is never skipped, even if the source did not change.
+    pub fn failed(&self, error: &Error) {
+        self.reader.forget();
+        record_failure(&mut self.health.lock(), error);
+    }
+
+    pub fn health(&self) -> SourceHealth {
+        self.health.lock().clone()
+    }
+
+    /// The wait before the next read, longer after failed reads.
+    /// The backoff is jittered, so sources that failed together do not retry together.
+    pub fn next_delay(&self) -> Duration {
+        let failures = self.health.lock().failures;
+        let delay = self.policy.delay(failures);
+        if failures == 0 {
+            return delay;
+        }
+        delay / 2 + delay.mul_f64(rand::random_range(0.0..0.5))
+    }
+}
+
+fn record_failure(health: &mut SourceHealth, error: &Error) {
+    health.last_error = Some(SourceError {
+        at: unix_now(),
+        message: error.to_string(),
+    });
+    health.failures = health.failures.saturating_add(1);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WEBCAST: &str = "../../tests/inputs/webcast_jones.zip";
+
+    #[tokio::test]
+    async fn test_health() {
+        let source = Source::webcast(WEBCAST);
+        assert_eq!(source.health().kind, "local_zip");
+
+        source.load().await.unwrap();
+        let health = source.health();
+        assert!(health.last_success.is_some());
+        assert!(health.latency_ms.is_some());
+        assert_eq!(source.next_delay(), Duration::from_secs(1));
+
+        assert!(source.load_if_changed().await.unwrap().is_some());
+        assert!(source.load_if_changed().await.unwrap().is_none());
+        assert!(source.health().last_success.is_some());
+
+        let missing = Source::webcast("../../tests/inputs/missing.zip");
+        assert!(missing.load().await.is_err());
+        assert!(missing.load().await.is_err());
+        let health = missing.health();
+        assert_eq!(health.failures, 2);
+        assert!(health.last_error.is_some());
+        let delay = missing.next_delay();
+        assert!(delay >= Duration::from_secs(2) && delay < Duration::from_secs(4));
+    }
+
+    #[tokio::test]
+    async fn test_stdin_pipe_skips_unchanged() {
+        let state = webcast_source(WEBCAST).load().await.unwrap();
+        let (tx, latest) = watch::channel(None);
+        let pipe = StdinPipe {
+            latest: Mutex::new(latest),
+        };
+
+        assert!(pipe.load_if_changed().await.unwrap().is_none());
+        assert!(pipe.load().await.is_err());
+
+        tx.send(Some(serde_json::to_string(&state).unwrap()))
+            .unwrap();
+        assert!(pipe.load_if_changed().await.unwrap().is_some());
+        assert!(pipe.load_if_changed().await.unwrap().is_none());
+        assert!(pipe.load().await.is_ok());
+
+        pipe.forget();
+        assert!(pipe.load_if_changed().await.unwrap().is_some());
+
+        tx.send(Some(serde_json::to_string(&state).unwrap()))
+            .unwrap();
+        drop(tx);
+        assert!(pipe.load_if_changed().await.unwrap().is_some());
+        assert!(pipe.load_if_changed().await.unwrap().is_none());
+    }
+}
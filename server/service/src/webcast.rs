@@ -1,42 +1,91 @@
 use crate::dataio::{read_contest, read_runs};
 use crate::errors::ServiceResult;
+use crate::source::webcast_source;
 use data::contest_state::ContestState;
 use std::io::Read;
+use std::path::Path;
 use std::string::FromUtf8Error;
 use thiserror::Error;
 use zip;
 
 #[derive(Debug, Error)]
-#[error(
-    "failed to read from BOCA_URL: {:?}\n{}\n{}",
-    path,
-    reqwest_err,
-    file_err
-)]
-pub struct FetchErr {
-    path: String,
-    file_err: std::io::Error,
-    reqwest_err: reqwest::Error,
-}
-
-async fn read_bytes_from_path(path: &str) -> Result<Vec<u8>, FetchErr> {
-    read_bytes_from_url(path).await.or_else(|reqwest_err| {
-        read_bytes_from_file(path).map_err(|file_err| FetchErr {
-            path: path.to_string(),
-            file_err,
-            reqwest_err,
-        })
+pub enum FetchErr {
+    /// The url is left out, BOCA's webcast url carries its key.
+    #[error("failed to fetch:\n{0}")]
+    Http(reqwest::Error),
+    #[error("failed to read {path:?}:\n{error}")]
+    File { path: String, error: std::io::Error },
+}
+
+/// Whether the source is read over HTTP, instead of from the local filesystem.
+pub(crate) fn is_url(uri: &str) -> bool {
+    uri.starts_with("http://") || uri.starts_with("https://")
+}
+
+pub(crate) async fn read_bytes_from_path(path: &str) -> Result<Vec<u8>, FetchErr> {
+    if is_url(path) {
+        read_bytes_from_url(path).await
+    } else {
+        read_bytes_from_file(path).await
+    }
+}
+
+pub(crate) async fn read_bytes_from_file(path: &str) -> Result<Vec<u8>, FetchErr> {
+    tokio::fs::read(path).await.map_err(|error| FetchErr::File {
+        path: path.to_string(),
+        error,
     })
 }
 
-fn read_bytes_from_file(path: &str) -> Result<Vec<u8>, std::io::Error> {
-    std::fs::read(path)
+pub(crate) async fn read_bytes_from_url(uri: &str) -> Result<Vec<u8>, FetchErr> {
+    async {
+        let resp = reqwest::get(uri).await?.error_for_status()?.bytes().await?;
+        Ok(resp.into())
+    }
+    .await
+    .map_err(|error: reqwest::Error| FetchErr::Http(error.without_url()))
+}
+
+#[derive(Debug, Clone, Default)]
+/// The validators of the last response, sent back so the server can answer 304.
+pub(crate) struct Validators {
+    etag: Option<reqwest::header::HeaderValue>,
+    last_modified: Option<reqwest::header::HeaderValue>,
 }
 
-async fn read_bytes_from_url(uri: &str) -> Result<Vec<u8>, reqwest::Error> {
-    let resp = reqwest::get(uri).await?.bytes().await?;
+/// Downloads the url, unless it did not change since the response `validators` came from.
+/// Returns `None` when the server answers `304 Not Modified`,
+/// or the body with the validators of the new response.
+pub(crate) async fn read_bytes_from_url_if_modified(
+    client: &reqwest::Client,
+    uri: &str,
+    validators: &Validators,
+) -> Result<Option<(Vec<u8>, Validators)>, FetchErr> {
+    use reqwest::{StatusCode, header};
 
-    Ok(resp.into())
+    async {
+        let mut request = client.get(uri);
+        if let Some(etag) = &validators.etag {
+            request = request.header(header::IF_NONE_MATCH, etag);
+        }
+        if let Some(last_modified) = &validators.last_modified {
+            request = request.header(header::IF_MODIFIED_SINCE, last_modified);
+        }
+
+        let resp = request.send().await?;
+        if resp.status() == StatusCode::NOT_MODIFIED {
+            return Ok(None);
+        }
+        let resp = resp.error_for_status()?;
+
+        let validators = Validators {
+            etag: resp.headers().get(header::ETAG).cloned(),
+            last_modified: resp.headers().get(header::LAST_MODIFIED).cloned(),
+        };
+        Ok(Some((resp.bytes().await?.into(), validators)))
+    }
+    .await
+    .map_err(|error: reqwest::Error| FetchErr::Http(error.without_url()))
 }
 
 #[derive(Debug, Error)]
@@ -78,13 +127,27 @@ fn read_from_zip(
         .or_else(|_| try_read_from_zip(zip, &format!("webcast/{}", name)))
 }
 
-pub async fn load_data_from_url_maybe(uri: &str) -> ServiceResult<ContestState> {
+/// Reads the `contest` file of BOCA's webcast as text, from an url, a zip file or a directory.
+pub async fn read_webcast_contest(uri: &str) -> ServiceResult<String> {
+    if Path::new(uri).is_dir() {
+        return Ok(read_webcast_dir(Path::new(uri)).await?.contest);
+    }
     let zip_data = read_bytes_from_path(uri).await?;
+    let mut zip = zip::ZipArchive::new(std::io::Cursor::new(&zip_data))?;
+    Ok(read_from_zip(&mut zip, "contest")?)
+}
+
+/// Reads BOCA's webcast once, from an url, a zip file or an extracted directory.
+pub async fn load_data_from_url_maybe(uri: &str) -> ServiceResult<ContestState> {
+    webcast_source(uri).load().await
+}
 
-    let reader = std::io::Cursor::new(&zip_data);
+/// Parses the webcast zip, with the `time`, `contest` and `runs` files.
+pub(crate) fn read_webcast_zip(zip_data: &Vec<u8>) -> ServiceResult<ContestState> {
+    let reader = std::io::Cursor::new(zip_data);
     let mut zip = zip::ZipArchive::new(reader)?;
 
-    let time_data: i64 = read_from_zip(&mut zip, "time")?.parse()?;
+    let time_data: i64 = read_from_zip(&mut zip, "time")?.trim().parse()?;
 
     let contest_data = read_from_zip(&mut zip, "contest")?;
     let contest_data = read_contest(&contest_data)?;
@@ -98,3 +161,41 @@ pub async fn load_data_from_url_maybe(uri: &str) -> ServiceResult<ContestState>
         contest: contest_data,
     })
 }
+
+/// The webcast files, as read from a directory.
+pub(crate) struct WebcastFiles {
+    pub time: String,
+    pub contest: String,
+    pub runs: String,
+}
+
+impl WebcastFiles {
+    pub fn parse(&self) -> ServiceResult<ContestState> {
+        Ok(ContestState {
+            runs: read_runs(&self.runs)?,
+            time: self.time.trim().parse()?,
+            contest: read_contest(&self.contest)?,
+        })
+    }
+}
+
+/// Reads the webcast files, extracted to a directory.
+pub(crate) async fn read_webcast_dir(dir: &Path) -> Result<WebcastFiles, FetchErr> {
+    let read = |name: &str| {
+        let path = dir.join(name);
+        async move {
+            tokio::fs::read_to_string(&path)
+                .await
+                .map_err(|error| FetchErr::File {
+                    path: path.display().to_string(),
+                    error,
+                })
+        }
+    };
+
+    Ok(WebcastFiles {
+        time: read("time").await?,
+        contest: read("contest").await?,
+        runs: read("runs").await?,
+    })
+}